@@ -11,6 +11,7 @@ use std::sync::Arc;
 use substrate::{
 	client::{api::HeaderBackend, consensus::pow::Error},
 	codec::{Decode, Encode},
+	primitives::runtime::traits::{BlakeTwo256, Hash as HashT},
 };
 
 #[derive(Debug, Decode, Encode)]
@@ -26,11 +27,29 @@ pub fn check_hash(hash: &Hash, difficulty: Difficulty) -> bool {
 	!overflowed
 }
 
+/// Returns if a new block should replace the best block of the same total difficulty.
+///
+/// The block with the lower hash of its pre-hash and seal wins, so that every node picks the same
+/// block regardless of which one it has seen first, while miners cannot predict the outcome
+/// without finding a seal. The pre-hash is included, since different blocks may have the same
+/// nonce. Identical blocks keep the one seen first.
+pub fn break_tie(
+	own_pre_hash: &Hash,
+	own_seal: &[u8],
+	new_pre_hash: &Hash,
+	new_seal: &[u8],
+) -> bool {
+	BlakeTwo256::hash_of(&(new_pre_hash, new_seal)) <
+		BlakeTwo256::hash_of(&(own_pre_hash, own_seal))
+}
+
+/// Number of blocks in a seed hash epoch.
+pub const SEEDHASH_EPOCH_BLOCKS: BlockNumber = 2048;
+/// Number of blocks a new seed hash lags behind its seed block.
+pub const SEEDHASH_EPOCH_LAG: BlockNumber = 64;
+
 /// Returns a block number for retrieving the seed hash.
 pub fn seed_height(height: BlockNumber) -> BlockNumber {
-	const SEEDHASH_EPOCH_BLOCKS: BlockNumber = 2048;
-	const SEEDHASH_EPOCH_LAG: BlockNumber = 64;
-
 	if height <= SEEDHASH_EPOCH_BLOCKS + SEEDHASH_EPOCH_LAG {
 		return 0;
 	}
@@ -38,6 +57,13 @@ pub fn seed_height(height: BlockNumber) -> BlockNumber {
 	(height - SEEDHASH_EPOCH_LAG - 1) & !(SEEDHASH_EPOCH_BLOCKS - 1)
 }
 
+/// Returns a block number for retrieving the seed hash of the epoch following the given height.
+///
+/// The seed block is known [`SEEDHASH_EPOCH_LAG`] blocks before the seed hash is switched.
+pub fn next_seed_height(height: BlockNumber) -> BlockNumber {
+	seed_height(height.saturating_add(SEEDHASH_EPOCH_BLOCKS))
+}
+
 /// Returns a seed hash for VM initialization with the given block number or hash.
 pub fn seed_hash<C>(client: &Arc<C>, parent: &BlockId) -> Result<Hash, Error<Block>>
 where
@@ -55,3 +81,91 @@ where
 		.map_err(Error::Client)?
 		.ok_or(Error::Environment(format!("Block hash not found: {:?}", seed_height)))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A block of the same total difficulty, given by its pre-hash and seal.
+	type Candidate = (Hash, Vec<u8>);
+
+	fn candidate(pre_hash: u8, nonce: Nonce) -> Candidate {
+		(Hash::repeat_byte(pre_hash), Seal { nonce }.encode())
+	}
+
+	fn break_tie_between(own: &Candidate, new: &Candidate) -> bool {
+		break_tie(&own.0, &own.1, &new.0, &new.1)
+	}
+
+	/// Picks the best block among candidates of the same total difficulty in the order they are
+	/// seen.
+	fn best(candidates: impl IntoIterator<Item = Candidate>) -> Option<Candidate> {
+		candidates.into_iter().reduce(|best, new| match break_tie_between(&best, &new) {
+			true => new,
+			false => best,
+		})
+	}
+
+	#[test]
+	fn break_tie_is_antisymmetric() {
+		for nonce in 0..100 {
+			let (a, b) = (candidate(1, nonce), candidate(1, nonce + 1));
+			assert_ne!(break_tie_between(&a, &b), break_tie_between(&b, &a));
+			assert!(!break_tie_between(&a, &a));
+		}
+	}
+
+	#[test]
+	fn equal_nonces_of_different_blocks_are_ordered() {
+		for nonce in 0..100 {
+			let (a, b) = (candidate(1, nonce), candidate(2, nonce));
+			assert_ne!(break_tie_between(&a, &b), break_tie_between(&b, &a));
+		}
+	}
+
+	#[test]
+	fn both_orderings_converge() {
+		for nonce in 0..100 {
+			let (a, b) = (candidate(1, nonce), candidate(2, nonce.wrapping_mul(7919)));
+			assert_eq!(best([a.clone(), b.clone()]), best([b, a]));
+		}
+	}
+
+	#[test]
+	fn all_orderings_converge() {
+		let candidates = (0..5).map(|i| candidate(i as u8, i * 1_000_003)).collect::<Vec<_>>();
+		let expected = best(candidates.clone());
+
+		// Every rotation and its reverse, simulating nodes receiving the blocks in different
+		// orders.
+		for shift in 0..candidates.len() {
+			let mut order = candidates.clone();
+			order.rotate_left(shift);
+			assert_eq!(best(order.clone()), expected);
+			order.reverse();
+			assert_eq!(best(order), expected);
+		}
+	}
+
+	#[test]
+	fn next_seed_height_is_the_seed_of_the_following_epoch() {
+		assert_eq!(next_seed_height(0), 0);
+		for k in 1..4 {
+			let start = k * SEEDHASH_EPOCH_BLOCKS;
+			assert_eq!(next_seed_height(start - 1), start);
+			assert_eq!(next_seed_height(start), start);
+			assert_eq!(next_seed_height(start + SEEDHASH_EPOCH_LAG), start);
+			assert_eq!(
+				next_seed_height(start + SEEDHASH_EPOCH_LAG + 1),
+				start + SEEDHASH_EPOCH_BLOCKS
+			);
+
+			// The next seed hash is switched to right after the lag.
+			assert_eq!(
+				seed_height(start + SEEDHASH_EPOCH_LAG + 1),
+				next_seed_height(start + SEEDHASH_EPOCH_LAG)
+			);
+			assert_eq!(seed_height(start + SEEDHASH_EPOCH_LAG), start - SEEDHASH_EPOCH_BLOCKS);
+		}
+	}
+}
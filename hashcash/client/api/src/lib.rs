@@ -5,6 +5,7 @@ mod preludes;
 use preludes::*;
 
 pub mod consensus;
+pub mod timestamp;
 
 pub use hashcash::primitives::core::{opaque::Block, Difficulty, Hash};
 pub use substrate::{client::consensus::pow::Version, primitives::consensus::pow::Seal};
@@ -90,3 +91,24 @@ pub struct BlockSubmitParams<B> {
 	/// The seal to be submitted.
 	pub seal: Vec<u8>,
 }
+
+/// The outcome of a successful block submission.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+	/// The block was imported but did not become the best block.
+	Imported,
+	/// The block was imported and became the best block.
+	Best,
+	/// The block was already in the chain.
+	AlreadyInChain,
+}
+
+/// A result of submitting a new block seal.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+pub struct BlockSubmitResult<H> {
+	/// The hash of the sealed block.
+	pub hash: H,
+	/// How the block was handled by the import pipeline.
+	pub status: ImportStatus,
+}
@@ -20,6 +20,8 @@ pub mod substrate {
 		pub mod consensus {
 			pub use sp_consensus_pow as pow;
 		}
+		pub use sp_inherents as inherents;
 		pub use sp_runtime as runtime;
+		pub use sp_timestamp as timestamp;
 	}
 }
@@ -0,0 +1,96 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Future time limit on the timestamps of imported blocks.
+//!
+//! The timestamp pallet rejects a block timestamped more than [`MAX_TIMESTAMP_DRIFT`] ahead of the
+//! timestamp in the inherent data. The verifier therefore provides the local time shifted by the
+//! difference, so that the pallet enforces the future time limit of the node instead.
+
+use crate::preludes::*;
+
+use std::time::Duration;
+use substrate::primitives::{
+	inherents::{self, InherentData, InherentIdentifier},
+	timestamp::{
+		self, InherentDataProvider as TimestampInherentDataProvider,
+		InherentError as TimestampInherentError, INHERENT_IDENTIFIER,
+	},
+};
+
+/// Maximum drift of a block timestamp ahead of the inherent data, as checked by the pallet.
+pub const MAX_TIMESTAMP_DRIFT: Duration = Duration::from_secs(30);
+
+/// A block timestamp exceeded the future time limit.
+#[derive(Debug, thiserror::Error)]
+#[error("Block timestamp is more than {0:?} ahead of the local clock")]
+pub struct TooFarInFuture(pub Duration);
+
+/// Timestamp inherent data provider for verifying blocks against a future time limit.
+///
+/// This must not be used to author blocks, as the provided timestamp is not the local time.
+pub struct FutureTimeLimit {
+	inner: TimestampInherentDataProvider,
+	limit: Duration,
+}
+
+impl FutureTimeLimit {
+	/// Create a provider rejecting blocks timestamped more than `limit` ahead of the system time.
+	pub fn from_system_time(limit: Duration) -> Self {
+		Self::new(timestamp::Timestamp::current(), limit)
+	}
+
+	/// Create a provider rejecting blocks timestamped more than `limit` ahead of `now`.
+	pub fn new(now: timestamp::Timestamp, limit: Duration) -> Self {
+		let shifted = (now.as_millis() + limit.as_millis() as u64)
+			.saturating_sub(MAX_TIMESTAMP_DRIFT.as_millis() as u64);
+		Self { inner: TimestampInherentDataProvider::new(shifted.into()), limit }
+	}
+
+	/// Returns the timestamp provided to the timestamp pallet.
+	pub fn timestamp(&self) -> timestamp::Timestamp {
+		self.inner.timestamp()
+	}
+}
+
+#[async_trait::async_trait]
+impl inherents::InherentDataProvider for FutureTimeLimit {
+	async fn provide_inherent_data(
+		&self,
+		inherent_data: &mut InherentData,
+	) -> Result<(), inherents::Error> {
+		self.inner.provide_inherent_data(inherent_data).await
+	}
+
+	async fn try_handle_error(
+		&self,
+		identifier: &InherentIdentifier,
+		error: &[u8],
+	) -> Option<Result<(), inherents::Error>> {
+		if *identifier != INHERENT_IDENTIFIER {
+			return None
+		}
+
+		match TimestampInherentError::try_from(identifier, error)? {
+			TimestampInherentError::TooFarInFuture =>
+				Some(Err(inherents::Error::Application(Box::new(TooFarInFuture(self.limit))))),
+			_ => self.inner.try_handle_error(identifier, error).await,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn timestamp_is_shifted_by_limit() {
+		let now = timestamp::Timestamp::new(1_000_000);
+
+		let ftl = FutureTimeLimit::new(now, Duration::from_secs(60));
+		assert_eq!(*ftl.timestamp(), 1_030_000);
+
+		let ftl = FutureTimeLimit::new(now, Duration::from_secs(5));
+		assert_eq!(*ftl.timestamp(), 975_000);
+	}
+}
@@ -8,28 +8,54 @@ use std::sync::Arc;
 use substrate::{
 	client::{
 		api::{AuxStore, HeaderBackend},
-		consensus::pow::{Error, PowAlgorithm},
+		consensus::pow::{Error, PowAlgorithm, WorkCache},
 	},
 	codec::{Decode, Encode},
 	primitives::{
 		api::ProvideRuntimeApi,
 		consensus::pow::{DifficultyApi, Seal},
+		runtime::traits::Header as HeaderT,
 	},
 };
 
+/// Maximum number of RandomX hashes kept for block import.
+const WORK_CACHE_SIZE: u32 = 4096;
+
 pub struct RandomXAlgorithm<C> {
 	client: Arc<C>,
+	verifier: randomx::Verifier,
+	/// Hashes keyed by seed hash, pre hash and nonce.
+	work_cache: WorkCache<(Hash, Hash, Nonce), Hash>,
 }
 
 impl<C> RandomXAlgorithm<C> {
-	pub fn new(client: Arc<C>) -> Self {
-		RandomXAlgorithm { client }
+	/// Creates an algorithm verifying hashes with the given verifier, which is shared with the
+	/// clones of the algorithm.
+	pub fn new(client: Arc<C>, verifier: randomx::Verifier) -> Self {
+		RandomXAlgorithm { client, verifier, work_cache: WorkCache::new(WORK_CACHE_SIZE) }
+	}
+
+	fn calculate_hash(
+		&self,
+		seed_hash: Hash,
+		pre_hash: Hash,
+		nonce: Nonce,
+	) -> Result<Hash, Error<Block>> {
+		self.work_cache.get_or_compute(&(seed_hash, pre_hash, nonce), || {
+			self.verifier
+				.calculate_hash(&seed_hash, (pre_hash, nonce).encode().as_slice())
+				.map_err(|_| Error::Environment("Failed to calculate a RandomX hash".to_string()))
+		})
 	}
 }
 
 impl<C> Clone for RandomXAlgorithm<C> {
 	fn clone(&self) -> Self {
-		RandomXAlgorithm { client: self.client.clone() }
+		RandomXAlgorithm {
+			client: self.client.clone(),
+			verifier: self.verifier.clone(),
+			work_cache: self.work_cache.clone(),
+		}
 	}
 }
 
@@ -47,6 +73,43 @@ where
 			.map_err(|e| Error::Client(e.into()))
 	}
 
+	fn break_tie(
+		&self,
+		own_pre_hash: &Hash,
+		own_seal: &Seal,
+		new_pre_hash: &Hash,
+		new_seal: &Seal,
+	) -> bool {
+		common::break_tie(own_pre_hash, own_seal, new_pre_hash, new_seal)
+	}
+
+	fn preliminary_verify(
+		&self,
+		header: &Header,
+		pre_hash: &Hash,
+		_pre_digest: Option<&[u8]>,
+		seal: &Seal,
+	) -> Result<Option<bool>, Error<Block>> {
+		let seal = match common::Seal::decode(&mut &seal[..]) {
+			Ok(seal) => seal,
+			Err(_) => return Ok(Some(false)),
+		};
+
+		// The hash can only be checked once the difficulty is known from the parent state, so it is
+		// left to import if the parent is not known yet.
+		let parent_hash = *header.parent_hash();
+		if self.client.number(parent_hash).map_err(Error::Client)?.is_none() {
+			return Ok(None);
+		}
+		let seed_hash = common::seed_hash(&self.client, &BlockId::hash(parent_hash))?;
+		let difficulty = self.difficulty(parent_hash)?;
+
+		// The hash is cached, so that it is not calculated again on import.
+		let work = self.calculate_hash(seed_hash, *pre_hash, seal.nonce)?;
+
+		Ok(Some(common::check_hash(&work, difficulty)))
+	}
+
 	fn verify(
 		&self,
 		parent: &BlockId,
@@ -61,9 +124,33 @@ where
 		};
 		let seed_hash = common::seed_hash(&self.client, parent)?;
 
-		let work = randomx::calculate_hash(&seed_hash, (pre_hash, seal.nonce).encode().as_slice())
-			.map_err(|_| Error::Environment("Failed to calculate a RandomX hash".to_string()))?;
+		let work = self.calculate_hash(seed_hash, *pre_hash, seal.nonce)?;
 
 		Ok(common::check_hash(&work, difficulty))
 	}
+
+	fn precompute(
+		&self,
+		pre_hash: &Hash,
+		seal: &Seal,
+		header: &Header,
+		queued: &[Header],
+	) -> Result<(), Error<Block>> {
+		let seal = match common::Seal::decode(&mut &seal[..]) {
+			Ok(seal) => seal,
+			Err(_) => return Ok(()),
+		};
+
+		// The seed block is usually imported already, but may be queued in the same batch.
+		let seed_height = common::seed_height(header.number().saturating_sub(1));
+		let seed_hash = match self.client.hash(seed_height).map_err(Error::Client)? {
+			Some(seed_hash) => seed_hash,
+			None => match queued.iter().find(|queued| *queued.number() == seed_height) {
+				Some(seed_header) => seed_header.hash(),
+				None => return Ok(()),
+			},
+		};
+
+		self.calculate_hash(seed_hash, *pre_hash, seal.nonce).map(|_| ())
+	}
 }
@@ -23,11 +23,12 @@ pub mod substrate {
 		pub mod consensus {
 			pub use sp_consensus_pow as pow;
 		}
+		pub use sp_runtime as runtime;
 	}
 	pub use parity_scale_codec as codec;
 }
 
 pub use hashcash::primitives::core::{
-	opaque::{Block, BlockId},
-	Difficulty, Hash,
+	opaque::{Block, BlockId, Header},
+	Difficulty, Hash, Nonce,
 };
@@ -47,8 +47,6 @@ where
 				let mut seed_hash = Hash::default();
 				let mut vm: Option<RandomXVm> = None;
 				let mut error: Option<Error> = None;
-				let mut is_new_vm = false;
-				let mut is_build_changed = false;
 
 				loop {
 					if error.is_some() {
@@ -61,8 +59,6 @@ where
 						std::thread::sleep(Duration::from_secs(1));
 					}
 
-					let mut nonce = nonce;
-
 					if !backend.bump() {
 						error = Some(Error::MetadataNotReady);
 						continue;
@@ -100,45 +96,29 @@ where
 								continue;
 							},
 						};
-						is_new_vm = true;
-						is_build_changed = false;
 					}
 
-					loop {
-						let new_version = backend.version();
-						if version != new_version {
-							version = new_version;
-							is_build_changed = true;
-							break;
-						}
-
-						if is_new_vm {
-							is_new_vm = false;
-							vm.as_mut()
-								.unwrap()
-								.calculate_hash_first(&(backend.pre_hash(), nonce).encode());
-						} else {
-							let seal = consensus::Seal { nonce };
-							if !is_build_changed {
-								nonce += threads_count as Nonce;
-							}
-
-							let hash = Hash::from(
-								vm.as_mut()
-									.unwrap()
-									.calculate_hash_next(&(backend.pre_hash(), nonce).encode()),
-							);
-
-							if !is_build_changed &&
-								consensus::check_hash(&hash, backend.difficulty())
-							{
-								if !backend.submit(hash, seal.encode()) {
-									warn!(target: LOG_TARGET, "error: mining-worker({}): failed to submit seal", thread_index);
-								}
-							}
-							is_build_changed = false;
+					let pre_hash = backend.pre_hash();
+					let difficulty = backend.difficulty();
+					let nonces = std::iter::successors(Some(nonce), |nonce| {
+						Some(nonce.wrapping_add(threads_count as Nonce))
+					});
+
+					let hashes = match vm.as_mut() {
+						Some(vm) => vm
+							.hashes(nonces, |nonce| (pre_hash, nonce).encode())
+							.cancel_when(|| backend.version() != version),
+						None => continue,
+					};
+					for (nonce, hash) in hashes {
+						let hash = Hash::from(hash);
+						if consensus::check_hash(&hash, difficulty) &&
+							!backend.submit(hash, consensus::Seal { nonce }.encode())
+						{
+							warn!(target: LOG_TARGET, "error: mining-worker({}): failed to submit seal", thread_index);
 						}
 					}
+					version = backend.version();
 				}
 			});
 		}
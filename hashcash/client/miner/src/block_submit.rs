@@ -3,23 +3,23 @@
 
 use crate::preludes::*;
 
-pub use hashcash::client::api::BlockSubmitParams;
+pub use hashcash::client::api::{BlockSubmitParams, BlockSubmitResult, ImportStatus};
 
 use parking_lot::Mutex;
 use std::sync::Arc;
 use substrate::{
 	client::consensus::{
-		pow::{PowIntermediate, INTERMEDIATE_KEY},
-		BlockImport, BlockImportParams, JustificationSyncLink, StateAction, StorageChanges,
+		pow::{
+			find_pre_digest, Error as PowError, PowAlgorithm, PowIntermediate, INTERMEDIATE_KEY,
+		},
+		BlockImport, BlockImportParams, ImportResult, JustificationSyncLink, StateAction,
+		StorageChanges,
 	},
 	codec::Error as CodecError,
 	primitives::{
 		api::{ApiError, ApiExt, CallApiAt, Core, ProvideRuntimeApi},
 		blockchain::HeaderBackend,
-		consensus::{
-			pow::{DifficultyApi, POW_ENGINE_ID},
-			BlockOrigin,
-		},
+		consensus::{pow::POW_ENGINE_ID, BlockOrigin, Error as ConsensusError},
 		runtime::{
 			traits::{Block as BlockT, Header},
 			DigestItem,
@@ -32,34 +32,76 @@ pub enum Error {
 	#[error(transparent)]
 	Codec(CodecError),
 	#[error(transparent)]
+	ConsensusPow(PowError<Block>),
+	#[error("Invalid seal")]
+	InvalidSeal,
+	#[error("Block import failed: {0:?}: {1}")]
+	Import(Hash, ConsensusError),
+	#[error("Block is known to be bad: {0:?}")]
+	KnownBad(Hash),
+	#[error("Parent of block is unknown: {0:?}")]
+	UnknownParent(Hash),
+	#[error("State of parent block is missing: {0:?}")]
+	MissingState(Hash),
+	#[error(transparent)]
 	RuntimeApi(#[from] ApiError),
 	#[error("{0}")]
 	StorageChanges(String),
 }
 
-pub struct BlockSubmit<C, I, L> {
+pub struct BlockSubmit<C, I, A, L> {
 	client: Arc<C>,
 	block_import: Arc<Mutex<I>>,
+	algorithm: A,
 	justification_sync_link: L,
 }
 
-impl<C, I, L> BlockSubmit<C, I, L> {
-	pub fn new(client: Arc<C>, block_import: I, justification_sync_link: L) -> Self {
-		Self { client, block_import: Arc::new(Mutex::new(block_import)), justification_sync_link }
+impl<C, I, A, L> BlockSubmit<C, I, A, L> {
+	pub fn new(client: Arc<C>, block_import: I, algorithm: A, justification_sync_link: L) -> Self {
+		Self {
+			client,
+			block_import: Arc::new(Mutex::new(block_import)),
+			algorithm,
+			justification_sync_link,
+		}
 	}
 }
 
 #[async_trait::async_trait]
-impl<C, I, L> crate::traits::BlockSubmit<Block> for BlockSubmit<C, I, L>
+impl<C, I, A, L> crate::traits::BlockSubmit<Block> for BlockSubmit<C, I, A, L>
 where
 	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + CallApiAt<Block>,
-	C::Api: DifficultyApi<Block, Difficulty> + ApiExt<Block>,
+	C::Api: ApiExt<Block>,
 	I: BlockImport<Block> + Send,
+	I::Error: Into<ConsensusError>,
+	A: PowAlgorithm<Block, Difficulty = Difficulty> + Send + Sync,
 	L: JustificationSyncLink<Block>,
 {
-	async fn submit_block(&self, params: BlockSubmitParams<Block>) -> Result<Hash, Error> {
+	async fn submit_block(
+		&self,
+		params: BlockSubmitParams<Block>,
+	) -> Result<BlockSubmitResult<Hash>, Error> {
 		let BlockSubmitParams { block, seal } = params;
 		let (header, body) = block.clone().deconstruct();
+		let parent_hash = *header.parent_hash();
+
+		// Checking the seal is much cheaper than executing the block, so bogus work is rejected
+		// before it can cost anything.
+		let difficulty = self.algorithm.difficulty(parent_hash).map_err(Error::ConsensusPow)?;
+		let pre_digest = find_pre_digest::<Block>(&header).map_err(Error::ConsensusPow)?;
+		let is_valid = self
+			.algorithm
+			.verify(
+				&BlockId::Hash(parent_hash),
+				&header.hash(),
+				pre_digest.as_deref(),
+				&seal,
+				difficulty,
+			)
+			.map_err(Error::ConsensusPow)?;
+		if !is_valid {
+			return Err(Error::InvalidSeal);
+		}
 
 		// CallApiAt::StateBackend doesn't implement Send, so we need to drop it before await.
 		let import_block = {
@@ -69,18 +111,15 @@ where
 			import_block.post_digests.push(seal);
 			import_block.body = Some(body);
 
-			let parent_hash = header.parent_hash();
 			let api = self.client.runtime_api();
-			api.execute_block(*parent_hash, block)?;
+			api.execute_block(parent_hash, block)?;
 
-			let state = self.client.state_at(*parent_hash).map_err(Error::RuntimeApi)?;
+			let state = self.client.state_at(parent_hash).map_err(Error::RuntimeApi)?;
 			let storage_changes =
-				api.into_storage_changes(&state, *parent_hash).map_err(Error::StorageChanges)?;
+				api.into_storage_changes(&state, parent_hash).map_err(Error::StorageChanges)?;
 			import_block.state_action =
 				StateAction::ApplyChanges(StorageChanges::Changes(storage_changes));
 
-			let difficulty =
-				self.client.runtime_api().difficulty(*parent_hash).map_err(Error::RuntimeApi)?;
 			let intermediate = PowIntermediate { difficulty: Some(difficulty) };
 			import_block.insert_intermediate(INTERMEDIATE_KEY, intermediate);
 
@@ -88,15 +127,23 @@ where
 		};
 
 		let header = import_block.post_header();
+		let hash = header.hash();
 		let mut block_import = self.block_import.lock();
 
-		if let Ok(res) = block_import.import_block(import_block).await {
-			res.handle_justification(
-				&header.hash(),
-				*header.number(),
-				&self.justification_sync_link,
-			);
-		}
-		Ok(header.hash())
+		let res = block_import
+			.import_block(import_block)
+			.await
+			.map_err(|e| Error::Import(hash, e.into()))?;
+		res.handle_justification(&hash, *header.number(), &self.justification_sync_link);
+
+		let status = match res {
+			ImportResult::Imported(aux) if aux.is_new_best => ImportStatus::Best,
+			ImportResult::Imported(_) => ImportStatus::Imported,
+			ImportResult::AlreadyInChain => ImportStatus::AlreadyInChain,
+			ImportResult::KnownBad => return Err(Error::KnownBad(hash)),
+			ImportResult::UnknownParent => return Err(Error::UnknownParent(hash)),
+			ImportResult::MissingState => return Err(Error::MissingState(hash)),
+		};
+		Ok(BlockSubmitResult { hash, status })
 	}
 }
@@ -7,6 +7,7 @@ pub use hashcash::client::api::MinerData;
 
 use hashcash::{client::api::consensus, primitives::coinbase};
 use parking_lot::Mutex;
+use schnellru::{ByLength, LruMap};
 use serde::{Deserialize, Serialize};
 use std::{sync::Arc, time::Duration};
 use substrate::{
@@ -20,7 +21,10 @@ use substrate::{
 			Environment, Proposer, SelectChain,
 		},
 		inherents::{CreateInherentDataProviders, InherentDataProvider},
-		runtime::{traits::Header, Digest, DigestItem},
+		runtime::{
+			traits::{Block as BlockT, Header},
+			Digest, DigestItem,
+		},
 	},
 };
 
@@ -36,6 +40,10 @@ pub enum Error {
 	EmptyShares,
 	#[error(transparent)]
 	Inherents(substrate::primitives::inherents::Error),
+	#[error("Unknown template: {0:?}")]
+	UnknownTemplate(Hash),
+	#[error("Stale template: {0:?}")]
+	StaleTemplate(Hash),
 	#[error(transparent)]
 	RuntimeApi(#[from] substrate::primitives::api::ApiError),
 	#[error("{0}")]
@@ -45,6 +53,37 @@ pub enum Error {
 	Other(#[from] Box<dyn std::error::Error + Sync + Send + 'static>),
 }
 
+/// Number of recent block templates that can still be submitted by id.
+const MAX_TEMPLATES: u32 = 32;
+
+/// Recently built block templates, keyed by their pre-hash.
+struct Templates(Mutex<LruMap<Hash, Block>>);
+
+impl Templates {
+	fn new(capacity: u32) -> Self {
+		Self(Mutex::new(LruMap::new(ByLength::new(capacity))))
+	}
+
+	fn insert(&self, block: Block) {
+		self.0.lock().insert(block.hash(), block);
+	}
+
+	/// Returns the template if it is still cached and builds on `best_hash`.
+	fn get(&self, template_id: &Hash, best_hash: &Hash) -> Result<Block, Error> {
+		let block = self
+			.0
+			.lock()
+			.get(template_id)
+			.cloned()
+			.ok_or(Error::UnknownTemplate(*template_id))?;
+
+		if block.header.parent_hash() != best_hash {
+			return Err(Error::StaleTemplate(*template_id));
+		}
+		Ok(block)
+	}
+}
+
 #[derive(Clone, Encode, Decode, Eq, PartialEq, Serialize, Deserialize)]
 pub struct MinerDataParams {
 	/// A block author.
@@ -75,6 +114,7 @@ pub struct MinerDataBuilder<C, CIDP, PF, PP, S> {
 	proposer_factory: Arc<Mutex<PF>>,
 	select_chain: S,
 	build_time: Duration,
+	templates: Templates,
 }
 
 impl<C, CIDP, PF, PP, S> MinerDataBuilder<C, CIDP, PF, PP, S> {
@@ -95,6 +135,7 @@ impl<C, CIDP, PF, PP, S> MinerDataBuilder<C, CIDP, PF, PP, S> {
 			proposer_factory,
 			select_chain,
 			build_time,
+			templates: Templates::new(MAX_TEMPLATES),
 		}
 	}
 }
@@ -164,6 +205,79 @@ where
 		let difficulty =
 			self.client.runtime_api().difficulty(*parent_hash).map_err(Error::RuntimeApi)?;
 
+		self.templates.insert(proposal.block.clone());
+
 		Ok(MinerData { block: proposal.block, difficulty, seed_hash })
 	}
+
+	fn template(&self, template_id: &Hash) -> Result<Block, Error> {
+		self.templates.get(template_id, &self.client.info().best_hash)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use hashcash::primitives::core::opaque::Header;
+	use substrate::primitives::runtime::traits::Header as _;
+
+	fn block(parent_hash: Hash, number: u32) -> Block {
+		let header = Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Default::default(),
+		);
+		Block::new(header, vec![])
+	}
+
+	#[test]
+	fn template_is_returned_while_cached() {
+		let templates = Templates::new(2);
+		let best_hash = Hash::repeat_byte(1);
+		let template = block(best_hash, 1);
+		templates.insert(template.clone());
+
+		assert_eq!(templates.get(&template.hash(), &best_hash).unwrap(), template);
+	}
+
+	#[test]
+	fn evicted_template_is_unknown() {
+		let templates = Templates::new(2);
+		let best_hash = Hash::repeat_byte(1);
+		let evicted = block(best_hash, 1);
+		templates.insert(evicted.clone());
+		templates.insert(block(best_hash, 2));
+		templates.insert(block(best_hash, 3));
+
+		assert!(matches!(
+			templates.get(&evicted.hash(), &best_hash),
+			Err(Error::UnknownTemplate(hash)) if hash == evicted.hash()
+		));
+	}
+
+	#[test]
+	fn template_on_old_parent_is_stale() {
+		let templates = Templates::new(2);
+		let template = block(Hash::repeat_byte(1), 1);
+		templates.insert(template.clone());
+
+		assert!(matches!(
+			templates.get(&template.hash(), &Hash::repeat_byte(2)),
+			Err(Error::StaleTemplate(hash)) if hash == template.hash()
+		));
+	}
+
+	#[test]
+	fn unknown_template_is_rejected() {
+		let templates = Templates::new(2);
+		templates.insert(block(Hash::repeat_byte(1), 1));
+
+		assert!(matches!(
+			templates.get(&Hash::repeat_byte(3), &Hash::repeat_byte(1)),
+			Err(Error::UnknownTemplate(hash)) if hash == Hash::repeat_byte(3)
+		));
+	}
 }
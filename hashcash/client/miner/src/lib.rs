@@ -10,6 +10,6 @@ pub mod traits;
 pub use hashcash_client_miner_worker as worker;
 
 pub use backend::MiningWorkerBackend;
-pub use block_submit::{BlockSubmit, BlockSubmitParams};
+pub use block_submit::{BlockSubmit, BlockSubmitParams, BlockSubmitResult, ImportStatus};
 pub use data::{MinerData, MinerDataBuilder, MinerDataBuilderParams, MinerDataParams};
 pub use worker::MiningWorker;
@@ -5,7 +5,8 @@ use crate::preludes::*;
 
 pub use crate::{block_submit::Error as BlockSubmitError, data::Error as MinerDataError};
 
-use hashcash::client::api::{BlockSubmitParams, MinerData};
+use hashcash::client::api::{BlockSubmitParams, BlockSubmitResult, MinerData};
+use std::sync::Arc;
 use substrate::primitives::runtime::traits::Block as BlockT;
 
 #[async_trait::async_trait]
@@ -13,10 +14,33 @@ pub trait MinerDataBuilder {
 	type Params;
 
 	async fn build(&self, params: Self::Params) -> Result<MinerData, MinerDataError>;
+
+	/// Returns a recently built block template by its pre-hash.
+	fn template(&self, template_id: &Hash) -> Result<Block, MinerDataError>;
+}
+
+/// Shares one builder, and its template cache, between several users.
+#[async_trait::async_trait]
+impl<T> MinerDataBuilder for Arc<T>
+where
+	T: MinerDataBuilder + Send + Sync + ?Sized,
+	T::Params: Send + 'static,
+{
+	type Params = T::Params;
+
+	async fn build(&self, params: Self::Params) -> Result<MinerData, MinerDataError> {
+		(**self).build(params).await
+	}
+
+	fn template(&self, template_id: &Hash) -> Result<Block, MinerDataError> {
+		(**self).template(template_id)
+	}
 }
 
 #[async_trait::async_trait]
 pub trait BlockSubmit<B: BlockT> {
-	async fn submit_block(&self, params: BlockSubmitParams<B>)
-		-> Result<B::Hash, BlockSubmitError>;
+	async fn submit_block(
+		&self,
+		params: BlockSubmitParams<B>,
+	) -> Result<BlockSubmitResult<B::Hash>, BlockSubmitError>;
 }
@@ -0,0 +1,69 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::preludes::*;
+
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Memory budget and flag overrides for RandomX.
+#[derive(Clone, Debug)]
+pub struct Config {
+	/// Maximum number of caches kept in memory. Values below one are treated as one.
+	pub cache_count: u32,
+	/// Maximum number of datasets kept in memory. Values below one are treated as one.
+	pub dataset_count: u32,
+	/// Maximum number of VMs to verify hashes. `None` means the number of available cores.
+	pub verifier_vms: Option<usize>,
+	/// Verifies hashes with light VMs only, so that no dataset is needed for verification.
+	pub light_mode: bool,
+	/// Disables JIT compilation even if it is supported.
+	pub disable_jit: bool,
+	/// Uses software AES even if hardware AES is supported.
+	pub soft_aes: bool,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			cache_count: 3,
+			dataset_count: 2,
+			verifier_vms: None,
+			light_mode: false,
+			disable_jit: false,
+			soft_aes: false,
+		}
+	}
+}
+
+impl Config {
+	pub(crate) fn cache_count(&self) -> u32 {
+		self.cache_count.max(1)
+	}
+
+	pub(crate) fn dataset_count(&self) -> u32 {
+		self.dataset_count.max(1)
+	}
+
+	pub(crate) fn verifier_vms(&self) -> usize {
+		self.verifier_vms.unwrap_or_else(available_cores).max(1)
+	}
+}
+
+pub(crate) fn available_cores() -> usize {
+	std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Sets the RandomX configuration.
+///
+/// It must be called before RandomX is used for the first time, and has no effect afterwards.
+pub fn set_config(config: Config) {
+	if CONFIG.set(config).is_err() {
+		log::warn!(target: LOG_TARGET, "RandomX is already configured");
+	}
+}
+
+pub(crate) fn config() -> &'static Config {
+	CONFIG.get_or_init(Config::default)
+}
@@ -4,14 +4,29 @@
 mod preludes;
 use preludes::*;
 
+mod config;
+mod metrics;
+mod prepare;
+mod verifier;
+
+pub use config::{set_config, Config};
+pub use metrics::register_metrics;
+pub use prepare::prepare_next_datasets;
+pub use verifier::Verifier;
+
 pub use hashcash::randomx::{
 	Error as RandomXError, RandomXCache, RandomXDataset, RandomXFlags, RandomXVm,
+	RANDOMX_DATASET_ITEM_SIZE,
 };
-use parking_lot::Mutex;
+use parking_lot::{Condvar, Mutex};
 use schnellru::{ByLength, LruMap};
 use std::{
 	cell::RefCell,
-	sync::{Arc, OnceLock},
+	collections::HashSet,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, OnceLock,
+	},
 };
 
 static CACHES: OnceLock<Arc<Mutex<LruMap<Hash, Arc<RandomXCache>>>>> = OnceLock::new();
@@ -19,6 +34,17 @@ static DATASETS: OnceLock<Arc<Mutex<LruMap<Hash, Arc<RandomXDataset>>>>> = OnceL
 
 static LARGE_PAGES: OnceLock<bool> = OnceLock::new();
 
+/// Seed hashes of the datasets being built on background threads.
+static PENDING_DATASETS: OnceLock<PendingDatasets> = OnceLock::new();
+
+/// Number of threads to initialize a dataset. Zero means all available cores.
+static INIT_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+struct PendingDatasets {
+	seed_hashes: Mutex<HashSet<Hash>>,
+	done: Condvar,
+}
+
 pub struct CachedVm {
 	pub seed_hash: Hash,
 	pub vm: RandomXVm,
@@ -42,88 +68,198 @@ impl From<RandomXError> for Error {
 }
 
 pub fn get_flags() -> RandomXFlags {
-	let use_large_pages = *LARGE_PAGES.get_or_init(|| {
-		match RandomXCache::new(RandomXFlags::default() | RandomXFlags::LargePages) {
+	let config = config::config();
+	let mut flags = RandomXFlags::default();
+	if config.disable_jit {
+		flags.remove(RandomXFlags::Jit);
+	}
+	if config.soft_aes {
+		flags.remove(RandomXFlags::HardAes);
+	}
+
+	let use_large_pages =
+		*LARGE_PAGES.get_or_init(|| match RandomXCache::new(flags | RandomXFlags::LargePages) {
 			Ok(_) => true,
 			Err(_) => {
 				log::debug!(target: LOG_TARGET, "RandomX allocation using large pages failed");
 				false
 			},
-		}
-	});
+		});
 	match use_large_pages {
-		true => RandomXFlags::default() | RandomXFlags::LargePages,
-		false => RandomXFlags::default(),
+		true => flags | RandomXFlags::LargePages,
+		false => flags,
+	}
+}
+
+/// Sets the number of threads to initialize a dataset. Zero means all available cores.
+pub fn set_init_threads(threads: usize) {
+	INIT_THREADS.store(threads, Ordering::Relaxed);
+}
+
+fn init_threads() -> usize {
+	match INIT_THREADS.load(Ordering::Relaxed) {
+		0 => config::available_cores(),
+		threads => threads,
 	}
 }
 
 pub fn get_or_init_cache(seed_hash: &Hash) -> Result<Arc<RandomXCache>, Error> {
-	let shared_caches = CACHES.get_or_init(|| Arc::new(Mutex::new(LruMap::new(ByLength::new(3)))));
+	let shared_caches = CACHES.get_or_init(|| {
+		Arc::new(Mutex::new(LruMap::new(ByLength::new(config::config().cache_count()))))
+	});
 	let mut shared_caches = shared_caches.lock();
 
 	if let Some(cache) = shared_caches.get(seed_hash) {
 		Ok(cache.clone())
 	} else {
-		let mut cache = RandomXCache::new(get_flags())?;
+		let mut cache = metrics::report(RandomXCache::new(get_flags()))?;
 		cache.init(&seed_hash[..]);
 
 		let cache = Arc::new(cache);
 		shared_caches.insert(*seed_hash, cache.clone());
+		metrics::report_caches(shared_caches.len());
 		Ok(cache)
 	}
 }
 
+fn shared_datasets() -> &'static Arc<Mutex<LruMap<Hash, Arc<RandomXDataset>>>> {
+	DATASETS.get_or_init(|| {
+		Arc::new(Mutex::new(LruMap::new(ByLength::new(config::config().dataset_count()))))
+	})
+}
+
+fn pending_datasets() -> &'static PendingDatasets {
+	PENDING_DATASETS.get_or_init(|| PendingDatasets {
+		seed_hashes: Mutex::new(HashSet::new()),
+		done: Condvar::new(),
+	})
+}
+
 pub fn get_dataset(seed_hash: &Hash) -> Result<Option<Arc<RandomXDataset>>, Error> {
-	let shared_datasets =
-		DATASETS.get_or_init(|| Arc::new(Mutex::new(LruMap::new(ByLength::new(2)))));
+	Ok(shared_datasets().lock().get(seed_hash).cloned())
+}
 
-	Ok(shared_datasets.lock().get(seed_hash).cloned())
+/// Returns whether the dataset for the given seed hash is kept, without refreshing it.
+pub(crate) fn has_dataset(seed_hash: &Hash) -> bool {
+	shared_datasets().lock().peek(seed_hash).is_some()
 }
 
 pub fn get_or_init_dataset(seed_hash: &Hash) -> Result<Arc<RandomXDataset>, Error> {
-	let shared_datasets =
-		DATASETS.get_or_init(|| Arc::new(Mutex::new(LruMap::new(ByLength::new(2)))));
-	let mut shared_datasets = shared_datasets.lock();
+	// Wait for the dataset being prepared in background rather than building it twice.
+	{
+		let pending = pending_datasets();
+		let mut seed_hashes = pending.seed_hashes.lock();
+		while seed_hashes.contains(seed_hash) {
+			pending.done.wait(&mut seed_hashes);
+		}
+	}
+
+	let mut shared_datasets = shared_datasets().lock();
 
 	if let Some(dataset) = shared_datasets.get(seed_hash) {
 		Ok(dataset.clone())
 	} else {
 		let cache = get_or_init_cache(seed_hash)?;
 
-		let mut dataset = RandomXDataset::new(get_flags())?;
-		dataset.init(&cache);
+		let mut dataset = metrics::report(RandomXDataset::new(get_flags()))?;
+		dataset.init_parallel(&cache, init_threads());
 
 		let dataset = Arc::new(dataset);
 		shared_datasets.insert(*seed_hash, dataset.clone());
+		metrics::report_datasets(shared_datasets.len());
 		Ok(dataset)
 	}
 }
 
-pub fn calculate_hash(seed_hash: &Hash, input: &[u8]) -> Result<Hash, Error> {
-	let flags = match get_flags() {
+/// Starts building the dataset for the given seed hash on a background thread, unless it has
+/// already been built or is being built.
+///
+/// Nothing is built if only one dataset is kept, as inserting it would evict the current one.
+pub fn prepare_dataset(seed_hash: Hash) {
+	if config::config().dataset_count() < 2 {
+		return;
+	}
+	if matches!(get_dataset(&seed_hash), Ok(Some(_))) {
+		return;
+	}
+	if !pending_datasets().seed_hashes.lock().insert(seed_hash) {
+		return;
+	}
+	log::info!(target: LOG_TARGET, "Preparing RandomX dataset: {:?}", seed_hash);
+
+	let spawned = std::thread::Builder::new().name("randomx-prepare".into()).spawn(move || {
+		// Unlike `get_or_init_dataset`, the shared datasets are not locked while building, so that
+		// the current dataset remains available.
+		let dataset = get_or_init_cache(&seed_hash).and_then(|cache| {
+			let mut dataset = metrics::report(RandomXDataset::new(get_flags()))?;
+			dataset.init_parallel(&cache, init_threads());
+			Ok(dataset)
+		});
+		match dataset {
+			Ok(dataset) => {
+				let mut shared_datasets = shared_datasets().lock();
+				shared_datasets.insert(seed_hash, Arc::new(dataset));
+				metrics::report_datasets(shared_datasets.len());
+				drop(shared_datasets);
+				log::info!(target: LOG_TARGET, "RandomX dataset prepared: {:?}", seed_hash);
+			},
+			Err(e) => log::warn!(target: LOG_TARGET, "Failed to prepare RandomX dataset: {:?}", e),
+		}
+
+		let pending = pending_datasets();
+		pending.seed_hashes.lock().remove(&seed_hash);
+		pending.done.notify_all();
+	});
+
+	if let Err(e) = spawned {
+		log::warn!(target: LOG_TARGET, "Failed to spawn a thread to prepare dataset: {}", e);
+		let pending = pending_datasets();
+		pending.seed_hashes.lock().remove(&seed_hash);
+		pending.done.notify_all();
+	}
+}
+
+/// Returns the flags for VMs verifying hashes, which must not write and execute the same memory.
+pub(crate) fn verify_flags() -> RandomXFlags {
+	match get_flags() {
 		flags if flags.contains(RandomXFlags::Jit) => flags | RandomXFlags::Secure,
 		flags => flags,
-	};
-	match FAST_VM.with_borrow_mut(|cached| match cached {
-		Some(cached) if &cached.seed_hash == seed_hash =>
-			Ok::<_, Error>(Hash::from(cached.vm.calculate_hash(input))),
-		_ => match get_dataset(seed_hash)? {
-			Some(dataset) => {
-				let mut vm = RandomXVm::new(flags | RandomXFlags::FullMem, None, Some(dataset))?;
-				let hash = Hash::from(vm.calculate_hash(input));
-				*cached = Some(CachedVm { seed_hash: *seed_hash, vm });
-				Ok(hash)
+	}
+}
+
+/// Calculates a RandomX hash value with VMs local to the current thread.
+///
+/// Block import should use [`Verifier`] instead, which bounds the number of VMs.
+pub fn calculate_hash(seed_hash: &Hash, input: &[u8]) -> Result<Hash, Error> {
+	let flags = verify_flags();
+	let fast_hash = match config::config().light_mode {
+		true => Err(Error::DatasetNotFound),
+		false => FAST_VM.with_borrow_mut(|cached| match cached {
+			Some(cached) if &cached.seed_hash == seed_hash =>
+				Ok::<_, Error>(Hash::from(cached.vm.calculate_hash(input))),
+			_ => match get_dataset(seed_hash)? {
+				Some(dataset) => {
+					let mut vm = metrics::report(RandomXVm::new(
+						flags | RandomXFlags::FullMem,
+						None,
+						Some(dataset),
+					))?;
+					let hash = Hash::from(vm.calculate_hash(input));
+					*cached = Some(CachedVm { seed_hash: *seed_hash, vm });
+					Ok(hash)
+				},
+				None => Err(Error::DatasetNotFound),
 			},
-			None => Err(Error::DatasetNotFound),
-		},
-	}) {
+		}),
+	};
+	match fast_hash {
 		Ok(hash) => Ok(hash),
 		Err(_) => LIGHT_VM.with_borrow_mut(|cached| match cached {
 			Some(cached) if &cached.seed_hash == seed_hash =>
 				Ok(Hash::from(cached.vm.calculate_hash(input))),
 			_ => {
 				let cache = get_or_init_cache(seed_hash)?;
-				let mut vm = RandomXVm::new(flags, Some(cache), None)?;
+				let mut vm = metrics::report(RandomXVm::new(flags, Some(cache), None))?;
 				let hash = Hash::from(vm.calculate_hash(input));
 				*cached = Some(CachedVm { seed_hash: *seed_hash, vm });
 				Ok(hash)
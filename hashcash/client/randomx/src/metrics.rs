@@ -0,0 +1,90 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{preludes::*, RandomXDataset, RandomXError, RANDOMX_DATASET_ITEM_SIZE};
+
+use std::sync::OnceLock;
+use substrate::prometheus::{
+	register, CounterVec, Gauge, GaugeVec, Opts, PrometheusError, Registry, U64,
+};
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+/// Memory used by a cache with the default RandomX configuration.
+const CACHE_SIZE: u64 = 256 << 20;
+/// Memory used by the scratchpad of a VM with the default RandomX configuration.
+const VM_SIZE: u64 = 2 << 20;
+
+struct Metrics {
+	allocation_failures: CounterVec<U64>,
+	memory: GaugeVec<U64>,
+	verifier_vms: Gauge<U64>,
+}
+
+/// Registers RandomX metrics to the given registry.
+pub fn register_metrics(registry: &Registry) -> Result<(), PrometheusError> {
+	let metrics = Metrics {
+		allocation_failures: register(
+			CounterVec::new(
+				Opts::new(
+					"hashcash_randomx_allocation_failures_total",
+					"Number of failed RandomX allocations",
+				),
+				&["kind"],
+			)?,
+			registry,
+		)?,
+		memory: register(
+			GaugeVec::new(
+				Opts::new(
+					"hashcash_randomx_memory_bytes",
+					"Approximate memory held by shared RandomX caches, datasets and verifier VMs",
+				),
+				&["kind"],
+			)?,
+			registry,
+		)?,
+		verifier_vms: register(
+			Gauge::new("hashcash_randomx_verifier_vms", "Number of VMs allocated by the verifier")?,
+			registry,
+		)?,
+	};
+	let _ = METRICS.set(metrics);
+	Ok(())
+}
+
+/// Counts an allocation failure, if any.
+pub(crate) fn report<T>(result: Result<T, RandomXError>) -> Result<T, RandomXError> {
+	if let (Err(e), Some(metrics)) = (&result, METRICS.get()) {
+		let kind = match e {
+			RandomXError::CacheNotAllocated => "cache",
+			RandomXError::DatasetNotAllocated => "dataset",
+			RandomXError::VmNotCreated => "vm",
+		};
+		metrics.allocation_failures.with_label_values(&[kind]).inc();
+	}
+	result
+}
+
+/// Reports the memory held by the shared caches.
+pub(crate) fn report_caches(count: usize) {
+	if let Some(metrics) = METRICS.get() {
+		metrics.memory.with_label_values(&["cache"]).set(count as u64 * CACHE_SIZE);
+	}
+}
+
+/// Reports the memory held by the shared datasets.
+pub(crate) fn report_datasets(count: usize) {
+	if let Some(metrics) = METRICS.get() {
+		let size = RandomXDataset::item_count() * RANDOMX_DATASET_ITEM_SIZE as u64;
+		metrics.memory.with_label_values(&["dataset"]).set(count as u64 * size);
+	}
+}
+
+/// Reports the number of VMs allocated by the verifier.
+pub(crate) fn report_verifier_vms(count: usize) {
+	if let Some(metrics) = METRICS.get() {
+		metrics.verifier_vms.set(count as u64);
+		metrics.memory.with_label_values(&["verifier"]).set(count as u64 * VM_SIZE);
+	}
+}
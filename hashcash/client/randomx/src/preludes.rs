@@ -4,10 +4,23 @@
 pub const LOG_TARGET: &str = "randomx";
 
 pub mod hashcash {
+	pub mod client {
+		pub use hashcash_client_api as api;
+	}
 	pub mod primitives {
 		pub use hashcash_primitives_core as core;
 	}
 	pub use hashcash_randomx as randomx;
 }
 
-pub use hashcash::primitives::core::Hash;
+pub mod substrate {
+	pub mod client {
+		pub use sc_client_api as api;
+	}
+	pub mod primitives {
+		pub use sp_runtime as runtime;
+	}
+	pub use substrate_prometheus_endpoint as prometheus;
+}
+
+pub use hashcash::primitives::core::{opaque::Block, Hash};
@@ -0,0 +1,74 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{config, preludes::*, prepare_dataset};
+
+use futures::StreamExt;
+use hashcash::{client::api::consensus, primitives::core::BlockNumber};
+use std::sync::Arc;
+use substrate::{
+	client::api::{BlockchainEvents, HeaderBackend},
+	primitives::runtime::traits::Header,
+};
+
+/// Prepares the dataset of the upcoming epoch as soon as its seed block is imported, so that
+/// miners can switch to the new seed hash without waiting for the dataset to be built.
+///
+/// The current and the next dataset are both kept in memory, so nothing is prepared unless at
+/// least two datasets are allowed.
+pub async fn prepare_next_datasets<C>(client: Arc<C>)
+where
+	C: BlockchainEvents<Block> + HeaderBackend<Block>,
+{
+	if config::config().dataset_count() < 2 {
+		log::info!(
+			target: LOG_TARGET,
+			"Not preparing RandomX datasets in advance, as only one dataset is kept",
+		);
+		return;
+	}
+
+	let mut import_notifications = client.import_notification_stream();
+
+	while let Some(notification) = import_notifications.next().await {
+		if !notification.is_new_best {
+			continue;
+		}
+
+		let Some(next_seed_height) = seed_to_prepare(*notification.header.number()) else {
+			continue;
+		};
+
+		match client.hash(next_seed_height) {
+			Ok(Some(seed_hash)) => prepare_dataset(seed_hash),
+			Ok(None) => (),
+			Err(e) => log::warn!(target: LOG_TARGET, "Failed to get the next seed hash: {}", e),
+		}
+	}
+}
+
+/// Returns the height of the seed block of the next epoch if it is known at the block at `number`,
+/// which is the case in the [`consensus::SEEDHASH_EPOCH_LAG`] blocks before the switch.
+fn seed_to_prepare(number: BlockNumber) -> Option<BlockNumber> {
+	let next_seed_height = consensus::next_seed_height(number);
+	(number >= next_seed_height).then_some(next_seed_height)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use consensus::{SEEDHASH_EPOCH_BLOCKS, SEEDHASH_EPOCH_LAG};
+
+	#[test]
+	fn datasets_are_prepared_from_seed_block_until_switch() {
+		for k in 1..4 {
+			let start = k * SEEDHASH_EPOCH_BLOCKS;
+			for number in start..=start + SEEDHASH_EPOCH_LAG {
+				assert_eq!(seed_to_prepare(number), Some(start), "block {}", number);
+			}
+			assert_eq!(seed_to_prepare(start + SEEDHASH_EPOCH_LAG + 1), None);
+			assert_eq!(seed_to_prepare(start + SEEDHASH_EPOCH_BLOCKS - 1), None);
+		}
+	}
+}
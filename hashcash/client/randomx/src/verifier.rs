@@ -0,0 +1,262 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{
+	config, get_dataset, get_or_init_cache, has_dataset, metrics, preludes::*, verify_flags,
+	CachedVm, Error, RandomXFlags, RandomXVm,
+};
+
+use parking_lot::{Condvar, Mutex};
+use std::sync::Arc;
+
+/// Verifies RandomX hashes with a bounded pool of VMs shared across threads.
+///
+/// A dataset is used only if it has already been built for mining, so the verifier never
+/// allocates a dataset by itself.
+#[derive(Clone)]
+pub struct Verifier {
+	pool: Arc<VmPool>,
+}
+
+struct VmPool {
+	max_vms: usize,
+	state: Mutex<PoolState>,
+	available: Condvar,
+}
+
+struct PoolState {
+	idle: Vec<IdleVm>,
+	/// Number of VMs either idle or in use.
+	count: usize,
+}
+
+struct IdleVm {
+	cached: CachedVm,
+	/// Whether the VM holds a dataset rather than a cache.
+	full_mem: bool,
+}
+
+impl PoolState {
+	/// Drops idle VMs whose dataset has been evicted, so that they don't keep it in memory.
+	fn prune(&mut self) {
+		let before = self.idle.len();
+		self.idle.retain(|idle| !idle.full_mem || has_dataset(&idle.cached.seed_hash));
+		if self.idle.len() < before {
+			self.count -= before - self.idle.len();
+			metrics::report_verifier_vms(self.count);
+		}
+	}
+}
+
+/// A slot of the pool, holding a VM once it has been created.
+///
+/// The VM returns to the pool on drop, unless the thread is panicking, in which case it is
+/// discarded. The slot is freed whenever the VM is not returned.
+struct PooledVm<'a> {
+	pool: &'a VmPool,
+	vm: Option<IdleVm>,
+}
+
+impl Drop for PooledVm<'_> {
+	fn drop(&mut self) {
+		let mut state = self.pool.state.lock();
+		match self.vm.take() {
+			Some(vm) if !std::thread::panicking() => state.idle.push(vm),
+			_ => {
+				state.count -= 1;
+				metrics::report_verifier_vms(state.count);
+			},
+		}
+		self.pool.available.notify_one();
+	}
+}
+
+impl Verifier {
+	/// Creates a verifier that allocates up to `max_vms` VMs.
+	pub fn new(max_vms: usize) -> Self {
+		let max_vms = max_vms.max(1);
+		log::info!(target: LOG_TARGET, "RandomX verifier uses up to {} VM(s)", max_vms);
+
+		Self {
+			pool: Arc::new(VmPool {
+				max_vms,
+				state: Mutex::new(PoolState { idle: Vec::new(), count: 0 }),
+				available: Condvar::new(),
+			}),
+		}
+	}
+
+	/// Returns the maximum number of VMs the verifier allocates.
+	pub fn max_vms(&self) -> usize {
+		self.pool.max_vms
+	}
+
+	/// Returns the number of VMs allocated by the verifier.
+	pub fn vm_count(&self) -> usize {
+		self.pool.state.lock().count
+	}
+
+	/// Calculates a RandomX hash value, waiting for a VM if all of them are in use.
+	pub fn calculate_hash(&self, seed_hash: &Hash, input: &[u8]) -> Result<Hash, Error> {
+		let mut pooled = self.acquire(seed_hash)?;
+		let vm = &mut pooled.vm.as_mut().expect("VM is created on acquire; qed").cached.vm;
+
+		Ok(Hash::from(vm.calculate_hash(input)))
+	}
+
+	fn acquire(&self, seed_hash: &Hash) -> Result<PooledVm<'_>, Error> {
+		let mut state = self.pool.state.lock();
+		loop {
+			state.prune();
+			if let Some(index) =
+				state.idle.iter().position(|idle| &idle.cached.seed_hash == seed_hash)
+			{
+				return Ok(PooledVm { pool: &self.pool, vm: Some(state.idle.swap_remove(index)) });
+			}
+			// An idle VM for another seed hash is replaced with a new one.
+			if state.idle.pop().is_some() {
+				break;
+			}
+			if state.count < self.pool.max_vms {
+				state.count += 1;
+				metrics::report_verifier_vms(state.count);
+				break;
+			}
+			self.pool.available.wait(&mut state);
+		}
+		drop(state);
+
+		// The slot is freed if the VM cannot be created.
+		let mut pooled = PooledVm { pool: &self.pool, vm: None };
+		pooled.vm = Some(create_vm(seed_hash)?);
+		Ok(pooled)
+	}
+}
+
+impl Default for Verifier {
+	fn default() -> Self {
+		Self::new(config::config().verifier_vms())
+	}
+}
+
+fn create_vm(seed_hash: &Hash) -> Result<IdleVm, Error> {
+	let flags = verify_flags();
+	let dataset = match config::config().light_mode {
+		true => None,
+		false => get_dataset(seed_hash)?,
+	};
+
+	let full_mem = dataset.is_some();
+	let vm = match dataset {
+		Some(dataset) =>
+			metrics::report(RandomXVm::new(flags | RandomXFlags::FullMem, None, Some(dataset)))?,
+		None => {
+			let cache = get_or_init_cache(seed_hash)?;
+			metrics::report(RandomXVm::new(flags, Some(cache), None))?
+		},
+	};
+	Ok(IdleVm { cached: CachedVm { seed_hash: *seed_hash, vm }, full_mem })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use std::{
+		sync::atomic::{AtomicUsize, Ordering},
+		thread,
+		time::Duration,
+	};
+
+	const INPUT: &[u8] = b"verifier test input";
+
+	fn seed(byte: u8) -> Hash {
+		Hash::repeat_byte(byte)
+	}
+
+	#[test]
+	fn vms_are_bounded_under_concurrent_use() {
+		let verifier = Verifier::new(2);
+		let peak = AtomicUsize::new(0);
+
+		thread::scope(|scope| {
+			for i in 0..8 {
+				let (verifier, peak) = (&verifier, &peak);
+				scope.spawn(move || {
+					for j in 0..4u8 {
+						verifier.calculate_hash(&seed(1), &[i, j]).unwrap();
+						peak.fetch_max(verifier.vm_count(), Ordering::Relaxed);
+					}
+				});
+			}
+		});
+
+		assert!(peak.load(Ordering::Relaxed) <= 2);
+		assert!(verifier.vm_count() <= 2);
+	}
+
+	#[test]
+	fn waiting_caller_is_woken_when_vm_is_returned() {
+		let verifier = Verifier::new(1);
+		let pooled = verifier.acquire(&seed(1)).unwrap();
+
+		let waiting = thread::spawn({
+			let verifier = verifier.clone();
+			move || verifier.calculate_hash(&seed(1), INPUT)
+		});
+		thread::sleep(Duration::from_millis(200));
+		assert!(!waiting.is_finished());
+
+		drop(pooled);
+		assert_eq!(
+			waiting.join().unwrap().unwrap(),
+			crate::calculate_hash(&seed(1), INPUT).unwrap()
+		);
+		assert_eq!(verifier.vm_count(), 1);
+	}
+
+	#[test]
+	fn slot_is_freed_after_panic() {
+		let verifier = Verifier::new(1);
+
+		let panicked = thread::spawn({
+			let verifier = verifier.clone();
+			move || {
+				let _pooled = verifier.acquire(&seed(1)).unwrap();
+				panic!("verification failed");
+			}
+		})
+		.join();
+		assert!(panicked.is_err());
+		assert_eq!(verifier.vm_count(), 0);
+
+		// The freed slot is available again.
+		verifier.calculate_hash(&seed(1), INPUT).unwrap();
+		assert_eq!(verifier.vm_count(), 1);
+	}
+
+	#[test]
+	fn idle_vm_of_another_seed_is_reused() {
+		let verifier = Verifier::new(2);
+
+		verifier.calculate_hash(&seed(1), INPUT).unwrap();
+		let hash = verifier.calculate_hash(&seed(2), INPUT).unwrap();
+
+		assert_eq!(hash, crate::calculate_hash(&seed(2), INPUT).unwrap());
+		assert_eq!(verifier.vm_count(), 1);
+	}
+
+	#[test]
+	fn idle_vms_of_evicted_datasets_are_pruned() {
+		let verifier = Verifier::new(2);
+		verifier.calculate_hash(&seed(1), INPUT).unwrap();
+		verifier.calculate_hash(&seed(3), INPUT).unwrap();
+
+		let mut state = verifier.pool.state.lock();
+		assert_eq!((state.idle.len(), state.count), (1, 1));
+		// Pretend the idle VM holds the dataset of a seed hash which has no dataset kept.
+		state.idle[0].full_mem = true;
+		state.prune();
+		assert_eq!((state.idle.len(), state.count), (0, 0));
+	}
+}
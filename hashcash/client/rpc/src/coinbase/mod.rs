@@ -0,0 +1,119 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::preludes::*;
+
+use hashcash::primitives::{
+	coinbase::{self, CoinbaseApi, EmissionApi},
+	core::{opaque::Block, AccountId, Balance, BlockNumber, Hash},
+};
+use jsonrpsee::{proc_macros::rpc, types::ErrorObjectOwned};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use substrate::primitives::{
+	api::{ApiError, ProvideRuntimeApi},
+	blockchain::HeaderBackend,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error(transparent)]
+	RuntimeApi(#[from] ApiError),
+}
+
+mod codes {
+	pub const BASE: i32 = 3000;
+	pub const RUNTIME_API: i32 = BASE + 1;
+}
+
+impl From<Error> for ErrorObjectOwned {
+	fn from(e: Error) -> Self {
+		match e {
+			Error::RuntimeApi(e) =>
+				ErrorObjectOwned::owned(codes::RUNTIME_API, e.to_string(), None::<()>),
+		}
+	}
+}
+
+/// Reward locked until the block at `unlock_at`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MaturingReward {
+	pub unlock_at: BlockNumber,
+	pub amount: Balance,
+}
+
+/// Coinbase rewards of an account which are locked until they mature.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedRewards {
+	/// Total locked amount.
+	pub locked: Balance,
+	/// Maturing rewards in ascending order of the unlock height.
+	pub maturing: Vec<MaturingReward>,
+}
+
+impl From<coinbase::LockedRewards<BlockNumber, Balance>> for LockedRewards {
+	fn from(rewards: coinbase::LockedRewards<BlockNumber, Balance>) -> Self {
+		Self {
+			locked: rewards.locked,
+			maturing: rewards
+				.maturing
+				.into_iter()
+				.map(|(unlock_at, amount)| MaturingReward { unlock_at, amount })
+				.collect(),
+		}
+	}
+}
+
+#[rpc(client, server)]
+pub trait CoinbaseApi {
+	/// Returns the locked and maturing coinbase rewards of `account`.
+	#[method(name = "coinbase_lockedRewards")]
+	fn locked_rewards(&self, account: AccountId, at: Option<Hash>) -> Result<LockedRewards, Error>;
+
+	/// Returns the block reward of the block at `height`.
+	#[method(name = "coinbase_blockReward")]
+	fn block_reward(&self, height: BlockNumber, at: Option<Hash>) -> Result<Balance, Error>;
+
+	/// Returns the total issuance projected at block `height` from the emission schedule.
+	#[method(name = "coinbase_projectedIssuance")]
+	fn projected_issuance(&self, height: BlockNumber, at: Option<Hash>) -> Result<Balance, Error>;
+}
+
+pub struct Coinbase<C> {
+	client: Arc<C>,
+}
+
+impl<C> Coinbase<C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client }
+	}
+}
+
+impl<C> Coinbase<C>
+where
+	C: HeaderBackend<Block>,
+{
+	fn at(&self, at: Option<Hash>) -> Hash {
+		at.unwrap_or_else(|| self.client.info().best_hash)
+	}
+}
+
+impl<C> CoinbaseApiServer for Coinbase<C>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: CoinbaseApi<Block, AccountId, BlockNumber, Balance>,
+	C::Api: EmissionApi<Block, BlockNumber, Balance>,
+{
+	fn locked_rewards(&self, account: AccountId, at: Option<Hash>) -> Result<LockedRewards, Error> {
+		Ok(self.client.runtime_api().locked_rewards(self.at(at), account)?.into())
+	}
+
+	fn block_reward(&self, height: BlockNumber, at: Option<Hash>) -> Result<Balance, Error> {
+		Ok(self.client.runtime_api().block_reward(self.at(at), height)?)
+	}
+
+	fn projected_issuance(&self, height: BlockNumber, at: Option<Hash>) -> Result<Balance, Error> {
+		Ok(self.client.runtime_api().projected_issuance(self.at(at), height)?)
+	}
+}
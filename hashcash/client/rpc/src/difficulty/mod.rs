@@ -0,0 +1,103 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::preludes::*;
+
+use hashcash::primitives::{
+	core::{opaque::Block, BlockNumber, Difficulty, Hash, Moment},
+	difficulty::DifficultyHistoryApi,
+};
+use jsonrpsee::{proc_macros::rpc, types::ErrorObjectOwned};
+use std::sync::Arc;
+use substrate::primitives::{
+	api::{ApiError, ProvideRuntimeApi},
+	blockchain::HeaderBackend,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error(transparent)]
+	RuntimeApi(#[from] ApiError),
+}
+
+mod codes {
+	pub const BASE: i32 = 2000;
+	pub const RUNTIME_API: i32 = BASE + 1;
+}
+
+impl From<Error> for ErrorObjectOwned {
+	fn from(e: Error) -> Self {
+		match e {
+			Error::RuntimeApi(e) =>
+				ErrorObjectOwned::owned(codes::RUNTIME_API, e.to_string(), None::<()>),
+		}
+	}
+}
+
+#[rpc(client, server)]
+pub trait DifficultyApi {
+	/// Returns the difficulty of a recent block, or null if it is out of the history.
+	#[method(name = "difficulty_difficultyAt")]
+	fn difficulty_at(
+		&self,
+		number: BlockNumber,
+		at: Option<Hash>,
+	) -> Result<Option<Difficulty>, Error>;
+
+	/// Returns the average solvetime of the last `window` blocks in milliseconds.
+	#[method(name = "difficulty_averageSolvetime")]
+	fn average_solvetime(&self, window: u32, at: Option<Hash>) -> Result<Option<Moment>, Error>;
+
+	/// Returns the network hashrate in hashes per second, estimated from the last `window` blocks.
+	#[method(name = "difficulty_networkHashrate")]
+	fn network_hashrate_estimate(
+		&self,
+		window: u32,
+		at: Option<Hash>,
+	) -> Result<Option<Difficulty>, Error>;
+}
+
+pub struct DifficultyHistory<C> {
+	client: Arc<C>,
+}
+
+impl<C> DifficultyHistory<C> {
+	pub fn new(client: Arc<C>) -> Self {
+		Self { client }
+	}
+}
+
+impl<C> DifficultyHistory<C>
+where
+	C: HeaderBackend<Block>,
+{
+	fn at(&self, at: Option<Hash>) -> Hash {
+		at.unwrap_or_else(|| self.client.info().best_hash)
+	}
+}
+
+impl<C> DifficultyApiServer for DifficultyHistory<C>
+where
+	C: ProvideRuntimeApi<Block> + HeaderBackend<Block> + Send + Sync + 'static,
+	C::Api: DifficultyHistoryApi<Block, BlockNumber, Moment, Difficulty>,
+{
+	fn difficulty_at(
+		&self,
+		number: BlockNumber,
+		at: Option<Hash>,
+	) -> Result<Option<Difficulty>, Error> {
+		Ok(self.client.runtime_api().difficulty_at(self.at(at), number)?)
+	}
+
+	fn average_solvetime(&self, window: u32, at: Option<Hash>) -> Result<Option<Moment>, Error> {
+		Ok(self.client.runtime_api().average_solvetime(self.at(at), window)?)
+	}
+
+	fn network_hashrate_estimate(
+		&self,
+		window: u32,
+		at: Option<Hash>,
+	) -> Result<Option<Difficulty>, Error> {
+		Ok(self.client.runtime_api().network_hashrate_estimate(self.at(at), window)?)
+	}
+}
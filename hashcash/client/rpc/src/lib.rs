@@ -3,4 +3,7 @@
 
 mod preludes;
 
+pub mod coinbase;
+pub mod difficulty;
 pub mod miner;
+pub mod pow;
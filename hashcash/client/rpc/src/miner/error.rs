@@ -3,7 +3,9 @@
 
 use crate::preludes::*;
 
+use hashcash::{client::miner::block_submit, primitives::core::Hash};
 use jsonrpsee::types::{ErrorObject, ErrorObjectOwned};
+use serde::Serialize;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,7 +14,9 @@ pub enum Error {
 	#[error(transparent)]
 	MinerData(hashcash::client::miner::data::Error),
 	#[error(transparent)]
-	BlockSubmit(hashcash::client::miner::block_submit::Error),
+	BlockSubmit(block_submit::Error),
+	#[error(transparent)]
+	StaleWork(hashcash::client::miner::data::Error),
 }
 
 mod codes {
@@ -20,6 +24,33 @@ mod codes {
 	pub const CODEC: i32 = BASE + 1;
 	pub const MINER_DATA: i32 = BASE + 2;
 	pub const BLOCK_SUBMIT: i32 = BASE + 3;
+	pub const STALE_WORK: i32 = BASE + 4;
+}
+
+/// Additional data of a block submission error telling why the block was not imported.
+#[derive(Debug, Serialize)]
+struct BlockSubmitErrorData {
+	reason: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	hash: Option<Hash>,
+}
+
+impl From<&block_submit::Error> for BlockSubmitErrorData {
+	fn from(e: &block_submit::Error) -> Self {
+		use block_submit::Error::*;
+		let (reason, hash) = match e {
+			Codec(_) => ("codec", None),
+			ConsensusPow(_) => ("consensus", None),
+			InvalidSeal => ("invalid_seal", None),
+			Import(hash, _) => ("import_failed", Some(*hash)),
+			KnownBad(hash) => ("known_bad", Some(*hash)),
+			UnknownParent(hash) => ("unknown_parent", Some(*hash)),
+			MissingState(hash) => ("missing_state", Some(*hash)),
+			RuntimeApi(_) => ("runtime_api", None),
+			StorageChanges(_) => ("storage_changes", None),
+		};
+		Self { reason, hash }
+	}
 }
 
 impl From<Error> for ErrorObjectOwned {
@@ -27,8 +58,12 @@ impl From<Error> for ErrorObjectOwned {
 		match e {
 			Error::Codec(e) => ErrorObjectOwned::owned(codes::CODEC, e.to_string(), None::<()>),
 			Error::MinerData(e) => ErrorObject::owned(codes::MINER_DATA, e.to_string(), None::<()>),
-			Error::BlockSubmit(e) =>
-				ErrorObject::owned(codes::BLOCK_SUBMIT, e.to_string(), None::<()>),
+			Error::BlockSubmit(e) => ErrorObject::owned(
+				codes::BLOCK_SUBMIT,
+				e.to_string(),
+				Some(BlockSubmitErrorData::from(&e)),
+			),
+			Error::StaleWork(e) => ErrorObject::owned(codes::STALE_WORK, e.to_string(), None::<()>),
 		}
 	}
 }
@@ -6,15 +6,40 @@ use crate::preludes::*;
 mod error;
 use error::Error;
 
+use futures::{
+	future::{self, FusedFuture},
+	FutureExt, StreamExt,
+};
+use futures_timer::Delay;
 use hashcash::{
 	client::miner::{
-		traits::{BlockSubmit, MinerDataBuilder},
-		BlockSubmitParams, MinerData, MinerDataParams,
+		traits::{BlockSubmit, MinerDataBuilder, MinerDataError},
+		BlockSubmitParams, BlockSubmitResult, MinerData, MinerDataParams,
 	},
-	primitives::core::{opaque::Block, AccountId, Bytes, Difficulty, Hash, H256},
+	primitives::core::{opaque::Block, AccountId, Bytes, Difficulty, Hash},
+};
+use jsonrpsee::{
+	core::{async_trait, SubscriptionResult},
+	proc_macros::rpc,
+	PendingSubscriptionSink, SubscriptionMessage,
 };
-use jsonrpsee::{core::async_trait, proc_macros::rpc};
-use substrate::codec::Decode;
+use std::{sync::Arc, time::Duration};
+use substrate::{
+	client::{api::BlockchainEvents, transaction_pool::api::TransactionPool},
+	codec::Decode,
+};
+
+const LOG_TARGET: &str = "rpc-miner";
+
+/// Minimum interval between two jobs rebuilt for transaction pool changes.
+///
+/// A new best block always rebuilds the job immediately.
+const TRANSACTIONS_REBUILD_INTERVAL: Duration = Duration::from_secs(2);
+
+enum JobEvent {
+	BestBlock,
+	Transactions,
+}
 
 #[rpc(client, server)]
 pub trait MinerApi {
@@ -25,28 +50,66 @@ pub trait MinerApi {
 		shares: Vec<(AccountId, Difficulty)>,
 	) -> Result<MinerData, Error>;
 
+	/// Submits a sealed block, returning its hash once imported.
+	///
+	/// Import failures are reported as errors. Use `miner_submitBlockWithStatus` to also learn
+	/// whether the block became the best block.
 	#[method(name = "miner_submitBlock")]
 	async fn submit_block(&self, data: Bytes) -> Result<Hash, Error>;
+
+	/// Submits a sealed block, returning its hash and how it was imported.
+	#[method(name = "miner_submitBlockWithStatus")]
+	async fn submit_block_with_status(&self, data: Bytes)
+		-> Result<BlockSubmitResult<Hash>, Error>;
+
+	/// Submits a seal for a block template built by `miner_getMinerData` or `miner_subscribeJobs`.
+	///
+	/// The template is identified by its pre-hash, so the block does not need to be sent back.
+	#[method(name = "miner_submitWork")]
+	async fn submit_work(
+		&self,
+		template_id: Hash,
+		seal: Bytes,
+	) -> Result<BlockSubmitResult<Hash>, Error>;
+
+	/// Subscribes to mining jobs.
+	///
+	/// A new job is pushed whenever a new best block is imported, and at most every two seconds
+	/// when the transaction pool changes.
+	#[subscription(
+		name = "miner_subscribeJobs" => "miner_jobs",
+		unsubscribe = "miner_unsubscribeJobs",
+		item = MinerData
+	)]
+	async fn subscribe_jobs(
+		&self,
+		author: AccountId,
+		shares: Vec<(AccountId, Difficulty)>,
+	) -> SubscriptionResult;
 }
 
-pub struct Miner<MD, BS> {
+pub struct Miner<C, P, MD, BS> {
+	client: Arc<C>,
+	pool: Arc<P>,
 	miner_data_builder: MD,
 	block_submit: BS,
 }
 
-impl<MD, BS> Miner<MD, BS>
+impl<C, P, MD, BS> Miner<C, P, MD, BS>
 where
 	MD: MinerDataBuilder<Params = MinerDataParams> + Send,
 	BS: BlockSubmit<Block> + Send,
 {
-	pub fn new(miner_data_builder: MD, block_submit: BS) -> Self {
-		Self { miner_data_builder, block_submit }
+	pub fn new(client: Arc<C>, pool: Arc<P>, miner_data_builder: MD, block_submit: BS) -> Self {
+		Self { client, pool, miner_data_builder, block_submit }
 	}
 }
 
 #[async_trait]
-impl<MD, BS> MinerApiServer for Miner<MD, BS>
+impl<C, P, MD, BS> MinerApiServer for Miner<C, P, MD, BS>
 where
+	C: BlockchainEvents<Block> + Send + Sync + 'static,
+	P: TransactionPool + 'static,
 	MD: MinerDataBuilder<Params = MinerDataParams> + Send + Sync + 'static,
 	BS: BlockSubmit<Block> + Send + Sync + 'static,
 {
@@ -61,7 +124,14 @@ where
 		.map_err(Error::MinerData)
 	}
 
-	async fn submit_block(&self, data: Bytes) -> Result<H256, Error> {
+	async fn submit_block(&self, data: Bytes) -> Result<Hash, Error> {
+		self.submit_block_with_status(data).await.map(|result| result.hash)
+	}
+
+	async fn submit_block_with_status(
+		&self,
+		data: Bytes,
+	) -> Result<BlockSubmitResult<Hash>, Error> {
 		let BlockSubmitParams { block, seal } =
 			BlockSubmitParams::<Block>::decode(&mut &data[..]).map_err(Error::Codec)?;
 
@@ -70,4 +140,67 @@ where
 			.await
 			.map_err(Error::BlockSubmit)
 	}
+
+	async fn submit_work(
+		&self,
+		template_id: Hash,
+		seal: Bytes,
+	) -> Result<BlockSubmitResult<Hash>, Error> {
+		let block = self.miner_data_builder.template(&template_id).map_err(|e| match e {
+			MinerDataError::UnknownTemplate(_) | MinerDataError::StaleTemplate(_) =>
+				Error::StaleWork(e),
+			e => Error::MinerData(e),
+		})?;
+
+		self.block_submit
+			.submit_block(BlockSubmitParams { block, seal: seal.to_vec() })
+			.await
+			.map_err(Error::BlockSubmit)
+	}
+
+	async fn subscribe_jobs(
+		&self,
+		pending: PendingSubscriptionSink,
+		author: AccountId,
+		shares: Vec<(AccountId, Difficulty)>,
+	) -> SubscriptionResult {
+		let sink = pending.accept().await?;
+
+		let best_blocks = self
+			.client
+			.import_notification_stream()
+			.filter(|notification| future::ready(notification.is_new_best))
+			.map(|_| JobEvent::BestBlock);
+		let transactions = self.pool.import_notification_stream().map(|_| JobEvent::Transactions);
+		let mut events = futures::stream::select(best_blocks, transactions);
+		let closed = sink.closed().fuse();
+		futures::pin_mut!(closed);
+
+		loop {
+			let params = MinerDataParams { author: author.clone(), shares: shares.clone() };
+			match self.miner_data_builder.build(params).await {
+				Ok(miner_data) => sink.send(SubscriptionMessage::from_json(&miner_data)?).await?,
+				Err(e) => log::warn!(target: LOG_TARGET, "Unable to build a mining job: {}", e),
+			}
+
+			// Wait for a new best block, or for transaction pool changes once the rebuild
+			// interval has elapsed.
+			let mut rebuild_delay = Delay::new(TRANSACTIONS_REBUILD_INTERVAL).fuse();
+			let mut transactions_pending = false;
+			loop {
+				futures::select! {
+					_ = closed => return Ok(()),
+					event = events.next() => match event {
+						Some(JobEvent::BestBlock) => break,
+						Some(JobEvent::Transactions) if rebuild_delay.is_terminated() => break,
+						Some(JobEvent::Transactions) => transactions_pending = true,
+						None => return Ok(()),
+					},
+					_ = rebuild_delay => if transactions_pending {
+						break
+					},
+				}
+			}
+		}
+	}
 }
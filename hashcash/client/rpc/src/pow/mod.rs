@@ -0,0 +1,83 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::preludes::*;
+
+use futures::{future, StreamExt};
+use hashcash::primitives::core::{opaque::Block, BlockNumber, Hash};
+use jsonrpsee::{
+	core::{async_trait, SubscriptionResult},
+	proc_macros::rpc,
+	PendingSubscriptionSink, SubscriptionMessage,
+};
+use serde::{Deserialize, Serialize};
+use substrate::client::consensus::pow::{ReorgEvent, ReorgNotifications};
+
+/// Block hash and number.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockRef {
+	pub hash: Hash,
+	pub number: BlockNumber,
+}
+
+/// Switch of the best chain to a block which does not extend the previous best block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reorg {
+	pub old_best: BlockRef,
+	pub new_best: BlockRef,
+	pub common_ancestor: BlockRef,
+	/// Number of blocks removed from the best chain.
+	pub depth: BlockNumber,
+}
+
+impl From<ReorgEvent<Block>> for Reorg {
+	fn from(event: ReorgEvent<Block>) -> Self {
+		let block_ref = |(hash, number)| BlockRef { hash, number };
+		Self {
+			old_best: block_ref(event.old_best),
+			new_best: block_ref(event.new_best),
+			common_ancestor: block_ref(event.common_ancestor),
+			depth: event.depth,
+		}
+	}
+}
+
+#[rpc(client, server)]
+pub trait PowApi {
+	/// Subscribes to reorgs of the best chain.
+	#[subscription(
+		name = "pow_subscribeReorgs" => "pow_reorg",
+		unsubscribe = "pow_unsubscribeReorgs",
+		item = Reorg
+	)]
+	async fn subscribe_reorgs(&self) -> SubscriptionResult;
+}
+
+pub struct Pow {
+	reorgs: ReorgNotifications<Block>,
+}
+
+impl Pow {
+	pub fn new(reorgs: ReorgNotifications<Block>) -> Self {
+		Self { reorgs }
+	}
+}
+
+#[async_trait]
+impl PowApiServer for Pow {
+	async fn subscribe_reorgs(&self, pending: PendingSubscriptionSink) -> SubscriptionResult {
+		let sink = pending.accept().await?;
+
+		let mut reorgs = self.reorgs.subscribe();
+		let closed = sink.closed();
+		futures::pin_mut!(closed);
+
+		while let future::Either::Right((Some(event), _)) =
+			future::select(&mut closed, reorgs.next()).await
+		{
+			sink.send(SubscriptionMessage::from_json(&Reorg::from(event))?).await?;
+		}
+		Ok(())
+	}
+}
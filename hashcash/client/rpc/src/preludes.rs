@@ -6,9 +6,23 @@ pub mod hashcash {
 		pub use hashcash_client_miner as miner;
 	}
 	pub mod primitives {
+		pub use hashcash_primitives::{coinbase, difficulty};
 		pub use hashcash_primitives_core as core;
 	}
 }
 pub mod substrate {
 	pub use parity_scale_codec as codec;
+	pub mod client {
+		pub use sc_client_api as api;
+		pub mod consensus {
+			pub use sc_consensus_pow as pow;
+		}
+		pub mod transaction_pool {
+			pub use sc_transaction_pool_api as api;
+		}
+	}
+	pub mod primitives {
+		pub use sp_api as api;
+		pub use sp_blockchain as blockchain;
+	}
 }
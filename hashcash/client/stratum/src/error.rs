@@ -0,0 +1,47 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{preludes::*, protocol::ErrorObject, JobId};
+
+use hashcash::client::miner::traits::BlockSubmitError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+	#[error("Parse error: {0}")]
+	Parse(serde_json::Error),
+	#[error("Method not found: {0}")]
+	MethodNotFound(String),
+	#[error("Invalid params: {0}")]
+	InvalidParams(serde_json::Error),
+	#[error("Job not found: {0}")]
+	JobNotFound(JobId),
+	#[error(transparent)]
+	BlockSubmit(BlockSubmitError),
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+}
+
+mod codes {
+	pub const PARSE: i32 = -32700;
+	pub const METHOD_NOT_FOUND: i32 = -32601;
+	pub const INVALID_PARAMS: i32 = -32602;
+	pub const INTERNAL: i32 = -32603;
+
+	pub const BASE: i32 = 1000;
+	pub const JOB_NOT_FOUND: i32 = BASE + 1;
+	pub const BLOCK_SUBMIT: i32 = BASE + 2;
+}
+
+impl From<Error> for ErrorObject {
+	fn from(e: Error) -> Self {
+		let code = match e {
+			Error::Parse(_) => codes::PARSE,
+			Error::MethodNotFound(_) => codes::METHOD_NOT_FOUND,
+			Error::InvalidParams(_) => codes::INVALID_PARAMS,
+			Error::JobNotFound(_) => codes::JOB_NOT_FOUND,
+			Error::BlockSubmit(_) => codes::BLOCK_SUBMIT,
+			Error::Io(_) => codes::INTERNAL,
+		};
+		ErrorObject { code, message: e.to_string() }
+	}
+}
@@ -0,0 +1,19 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+//! Stratum-like mining server for remote miners.
+//!
+//! The server speaks line-delimited JSON-RPC over TCP. A miner subscribes with
+//! `mining.subscribe`, receives `mining.notify` whenever the block template changes and submits
+//! found nonces with `mining.submit`. Only the pre-hash of a template is sent to the miner, so
+//! remote rigs never need to download full blocks.
+
+mod preludes;
+
+mod error;
+pub mod protocol;
+mod server;
+
+pub use error::Error;
+pub use protocol::{Job, JobId};
+pub use server::{StratumParams, StratumServer};
@@ -0,0 +1,30 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+pub const LOG_TARGET: &str = "stratum";
+
+pub mod hashcash {
+	pub mod client {
+		pub use hashcash_client_api as api;
+		pub use hashcash_client_miner as miner;
+	}
+	pub mod primitives {
+		pub use hashcash_primitives_core as core;
+	}
+}
+
+pub mod substrate {
+	pub mod client {
+		pub use sc_client_api as api;
+		pub mod consensus {
+			pub use sc_consensus_pow as pow;
+		}
+	}
+	pub mod primitives {
+		pub use sp_consensus as consensus;
+		pub use sp_runtime as runtime;
+	}
+	pub use parity_scale_codec as codec;
+}
+
+pub use hashcash::primitives::core::{opaque::Block, AccountId, Difficulty, Hash, Nonce, U256};
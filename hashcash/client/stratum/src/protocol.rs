@@ -0,0 +1,140 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::preludes::*;
+
+use hashcash::client::api::MinerData;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use substrate::primitives::runtime::traits::Block as BlockT;
+
+/// Subscribes to mining jobs. The result is the current job, if any.
+pub const METHOD_SUBSCRIBE: &str = "mining.subscribe";
+/// Submits a nonce found for a job.
+pub const METHOD_SUBMIT: &str = "mining.submit";
+/// Notifies a new job to subscribed miners.
+pub const METHOD_NOTIFY: &str = "mining.notify";
+
+/// An identifier of a mining job, unique within a server instance.
+pub type JobId = u64;
+
+/// A mining job pushed to remote miners.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+	/// The job identifier to be used for submission.
+	pub job_id: JobId,
+	/// The pre-hash of the block template to be mined.
+	pub pre_hash: Hash,
+	/// The seed hash for the mining algorithm.
+	pub seed_hash: Hash,
+	/// The maximum hash value that satisfies the mining difficulty.
+	pub target: U256,
+}
+
+impl Job {
+	pub fn new(job_id: JobId, miner_data: &MinerData) -> Self {
+		Self {
+			job_id,
+			pre_hash: miner_data.block.hash(),
+			seed_hash: miner_data.seed_hash,
+			target: target(miner_data.difficulty),
+		}
+	}
+}
+
+/// Returns the maximum hash value that satisfies the given difficulty.
+pub fn target(difficulty: Difficulty) -> U256 {
+	U256::max_value().checked_div(difficulty.into()).unwrap_or(U256::max_value())
+}
+
+/// Parameters of `mining.submit`.
+///
+/// Accepted either as an object or as a `[job_id, nonce]` array.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Submit {
+	pub job_id: JobId,
+	pub nonce: Nonce,
+}
+
+/// A request sent by a miner.
+#[derive(Debug, Deserialize)]
+pub struct Request {
+	#[serde(default)]
+	pub id: Value,
+	pub method: String,
+	#[serde(default)]
+	pub params: Value,
+}
+
+/// An error object of a response.
+#[derive(Debug, Serialize)]
+pub struct ErrorObject {
+	pub code: i32,
+	pub message: String,
+}
+
+/// A response to a miner's request.
+#[derive(Debug, Serialize)]
+pub struct Response {
+	pub jsonrpc: &'static str,
+	pub id: Value,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub result: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<ErrorObject>,
+}
+
+impl Response {
+	pub fn result(id: Value, result: Value) -> Self {
+		Self { jsonrpc: "2.0", id, result: Some(result), error: None }
+	}
+
+	pub fn error(id: Value, error: ErrorObject) -> Self {
+		Self { jsonrpc: "2.0", id, result: None, error: Some(error) }
+	}
+}
+
+/// A notification pushed to a subscribed miner.
+#[derive(Debug, Serialize)]
+pub struct Notification<T> {
+	pub jsonrpc: &'static str,
+	pub method: &'static str,
+	pub params: T,
+}
+
+impl<T> Notification<T> {
+	pub fn new(method: &'static str, params: T) -> Self {
+		Self { jsonrpc: "2.0", method, params }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn submit_is_parsed_from_object_or_array() {
+		let expected = Submit { job_id: 3, nonce: 42 };
+		let submit = serde_json::from_str::<Submit>(r#"{"job_id":3,"nonce":42}"#).unwrap();
+		assert_eq!(submit, expected);
+		assert_eq!(serde_json::from_str::<Submit>("[3,42]").unwrap(), expected);
+		assert!(serde_json::from_str::<Submit>("[3]").is_err());
+	}
+
+	#[test]
+	fn request_id_and_params_are_optional() {
+		let request = serde_json::from_str::<Request>(r#"{"method":"mining.subscribe"}"#).unwrap();
+		assert_eq!(request.method, METHOD_SUBSCRIBE);
+		assert_eq!(request.id, Value::Null);
+		assert_eq!(request.params, Value::Null);
+
+		assert!(serde_json::from_str::<Request>(r#"{"id":1}"#).is_err());
+	}
+
+	#[test]
+	fn target_shrinks_with_difficulty() {
+		assert_eq!(target(0), U256::max_value());
+		assert_eq!(target(1), U256::max_value());
+		assert_eq!(target(2), U256::max_value() / 2);
+	}
+}
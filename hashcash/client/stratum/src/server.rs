@@ -0,0 +1,627 @@
+// Copyright (c) Hisaishi Joe
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::{
+	error::Error,
+	preludes::*,
+	protocol::{self, Job, JobId, Notification, Request, Response, Submit},
+};
+
+use futures::StreamExt;
+use hashcash::client::{
+	api::consensus,
+	miner::{
+		traits::{BlockSubmit, MinerDataBuilder},
+		BlockSubmitParams, BlockSubmitResult, MinerData, MinerDataParams,
+	},
+};
+use log::*;
+use parking_lot::{Mutex, RwLock};
+use schnellru::{ByLength, LruMap};
+use serde_json::Value;
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use substrate::{
+	client::{api::BlockchainEvents, consensus::pow::UntilImportedOrTimeout},
+	codec::Encode,
+	primitives::{consensus::SyncOracle, runtime::traits::Block as BlockT},
+};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader},
+	net::{TcpListener, TcpStream},
+	sync::{broadcast, Semaphore},
+	time::{self, Instant},
+};
+
+/// Number of recent jobs that can still be submitted.
+const MAX_JOBS: u32 = 8;
+/// Maximum number of connected miners.
+const MAX_CONNECTIONS: usize = 256;
+/// Maximum length of a request line, including the newline.
+const MAX_LINE_LENGTH: usize = 16 * 1024;
+/// Maximum time to receive the rest of a line once it has started.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// Maximum time for a miner to subscribe after connecting.
+const SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct StratumParams<C, MD, BS, SO> {
+	/// The client to interact with the chain.
+	pub client: Arc<C>,
+	/// The builder for new block templates.
+	pub miner_data_builder: MD,
+	/// The submitter of mined blocks.
+	pub block_submit: BS,
+	/// The sync oracle that can give us the current sync status.
+	pub sync_oracle: SO,
+	/// Account for block mining rewards.
+	pub author: AccountId,
+	/// The address to listen for miners on.
+	pub listen_addr: SocketAddr,
+	/// Maximum time before a block template is rebuilt.
+	pub timeout: Duration,
+}
+
+struct Shared<MD, BS> {
+	miner_data_builder: MD,
+	block_submit: BS,
+	author: AccountId,
+	jobs: Mutex<LruMap<JobId, MinerData>>,
+	current: RwLock<Option<Job>>,
+	notifier: broadcast::Sender<Job>,
+}
+
+impl<MD, BS> Shared<MD, BS> {
+	fn new(miner_data_builder: MD, block_submit: BS, author: AccountId) -> Self {
+		let (notifier, _) = broadcast::channel(MAX_JOBS as usize);
+
+		Self {
+			miner_data_builder,
+			block_submit,
+			author,
+			jobs: Mutex::new(LruMap::new(ByLength::new(MAX_JOBS))),
+			current: RwLock::new(None),
+			notifier,
+		}
+	}
+
+	/// Notifies a new job for the template, unless it is the template of the current job.
+	fn update_job(&self, miner_data: MinerData) -> Option<Job> {
+		let pre_hash = miner_data.block.hash();
+		let mut current = self.current.write();
+		if current.as_ref().is_some_and(|job| job.pre_hash == pre_hash) {
+			return None;
+		}
+
+		let job_id = current.as_ref().map_or(1, |job| job.job_id + 1);
+		let job = Job::new(job_id, &miner_data);
+		self.jobs.lock().insert(job_id, miner_data);
+		*current = Some(job.clone());
+		// No receivers just means no miner is subscribed yet.
+		let _ = self.notifier.send(job.clone());
+		Some(job)
+	}
+}
+
+pub struct StratumServer<C, MD, BS, SO> {
+	client: Arc<C>,
+	sync_oracle: SO,
+	listen_addr: SocketAddr,
+	timeout: Duration,
+	shared: Arc<Shared<MD, BS>>,
+}
+
+impl<C, MD, BS, SO> StratumServer<C, MD, BS, SO>
+where
+	C: BlockchainEvents<Block> + Send + Sync + 'static,
+	MD: MinerDataBuilder<Params = MinerDataParams> + Send + Sync + 'static,
+	BS: BlockSubmit<Block> + Send + Sync + 'static,
+	SO: SyncOracle + Send + Sync + 'static,
+{
+	pub fn new(params: StratumParams<C, MD, BS, SO>) -> Self {
+		let StratumParams {
+			client,
+			miner_data_builder,
+			block_submit,
+			sync_oracle,
+			author,
+			listen_addr,
+			timeout,
+		} = params;
+
+		Self {
+			client,
+			sync_oracle,
+			listen_addr,
+			timeout,
+			shared: Arc::new(Shared::new(miner_data_builder, block_submit, author)),
+		}
+	}
+
+	/// Runs the server until the listener fails.
+	pub async fn run(self) {
+		let listener = match TcpListener::bind(self.listen_addr).await {
+			Ok(listener) => listener,
+			Err(e) => {
+				error!(target: LOG_TARGET, "Failed to listen on {}: {}", self.listen_addr, e);
+				return;
+			},
+		};
+		info!(target: LOG_TARGET, "⛏️  Stratum server listening on {}", self.listen_addr);
+
+		let Self { client, sync_oracle, timeout, shared, .. } = self;
+		let jobs = update_jobs(client, sync_oracle, timeout, shared.clone());
+		let accept = accept_connections(listener, shared, MAX_CONNECTIONS);
+
+		futures::future::join(jobs, accept).await;
+	}
+}
+
+/// Accepts miners, rejecting them while `max_connections` miners are connected.
+async fn accept_connections<MD, BS>(
+	listener: TcpListener,
+	shared: Arc<Shared<MD, BS>>,
+	max_connections: usize,
+) where
+	MD: Send + Sync + 'static,
+	BS: BlockSubmit<Block> + Send + Sync + 'static,
+{
+	let connections = Arc::new(Semaphore::new(max_connections));
+
+	loop {
+		match listener.accept().await {
+			Ok((stream, peer)) => {
+				let Ok(permit) = connections.clone().try_acquire_owned() else {
+					debug!(target: LOG_TARGET, "Too many miners connected, rejecting {}", peer);
+					continue;
+				};
+				debug!(target: LOG_TARGET, "Miner connected: {}", peer);
+				let shared = shared.clone();
+				tokio::spawn(async move {
+					handle_connection(shared, stream, peer).await;
+					drop(permit);
+				});
+			},
+			Err(e) => warn!(target: LOG_TARGET, "Failed to accept a miner: {}", e),
+		}
+	}
+}
+
+/// Rebuilds the block template on every import or timeout, and notifies a new job when the
+/// template has changed.
+async fn update_jobs<C, MD, BS, SO>(
+	client: Arc<C>,
+	sync_oracle: SO,
+	timeout: Duration,
+	shared: Arc<Shared<MD, BS>>,
+) where
+	C: BlockchainEvents<Block>,
+	MD: MinerDataBuilder<Params = MinerDataParams>,
+	SO: SyncOracle,
+{
+	let mut timer = UntilImportedOrTimeout::new(client.import_notification_stream(), timeout);
+
+	loop {
+		if sync_oracle.is_major_syncing() {
+			debug!(target: LOG_TARGET, "Skipping job update due to sync.");
+		} else {
+			let params = MinerDataParams {
+				author: shared.author.clone(),
+				shares: vec![(shared.author.clone(), 1)],
+			};
+			match shared.miner_data_builder.build(params).await {
+				Ok(miner_data) => {
+					shared.update_job(miner_data);
+				},
+				Err(e) => warn!(target: LOG_TARGET, "Unable to build a mining job: {}", e),
+			}
+		}
+
+		if timer.next().await.is_none() {
+			break;
+		}
+	}
+}
+
+async fn handle_connection<MD, BS>(shared: Arc<Shared<MD, BS>>, stream: TcpStream, peer: SocketAddr)
+where
+	BS: BlockSubmit<Block>,
+{
+	let (reader, mut writer) = stream.into_split();
+	let mut lines = LineReader::new(reader);
+	let mut jobs: Option<broadcast::Receiver<Job>> = None;
+	let subscribe_deadline = Instant::now() + SUBSCRIBE_TIMEOUT;
+
+	loop {
+		let message = tokio::select! {
+			line = lines.next_line() => match line {
+				Ok(Some(line)) if line.trim().is_empty() => continue,
+				Ok(Some(line)) => to_line(&handle_request(&shared, &mut jobs, &line).await),
+				Ok(None) => break,
+				Err(e) => {
+					debug!(target: LOG_TARGET, "Failed to read from {}: {}", peer, e);
+					break;
+				},
+			},
+			job = next_job(&mut jobs) => match job {
+				Ok(job) => to_line(&Notification::new(protocol::METHOD_NOTIFY, job)),
+				Err(broadcast::error::RecvError::Lagged(_)) => continue,
+				Err(broadcast::error::RecvError::Closed) => break,
+			},
+			_ = time::sleep_until(subscribe_deadline), if jobs.is_none() => {
+				debug!(target: LOG_TARGET, "Miner {} did not subscribe in time", peer);
+				break;
+			},
+		};
+
+		if let Err(e) = writer.write_all(message.as_bytes()).await {
+			debug!(target: LOG_TARGET, "Failed to write to {}: {}", peer, e);
+			break;
+		}
+	}
+	debug!(target: LOG_TARGET, "Miner disconnected: {}", peer);
+}
+
+/// Reader of newline-delimited requests of bounded length.
+struct LineReader<R> {
+	reader: BufReader<R>,
+	buf: Vec<u8>,
+	/// Deadline to complete the partially read line.
+	deadline: Option<Instant>,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+	fn new(reader: R) -> Self {
+		Self { reader: BufReader::new(reader), buf: Vec::new(), deadline: None }
+	}
+
+	/// Reads the next line, failing if it is longer than [`MAX_LINE_LENGTH`] or takes longer than
+	/// [`READ_TIMEOUT`] to complete.
+	///
+	/// This is cancel safe, as a partially read line is kept until the next call.
+	async fn next_line(&mut self) -> io::Result<Option<String>> {
+		loop {
+			let available = match self.deadline {
+				Some(deadline) =>
+					time::timeout_at(deadline, self.reader.fill_buf()).await.map_err(|_| {
+						io::Error::new(io::ErrorKind::TimedOut, "line not completed in time")
+					})??,
+				None => self.reader.fill_buf().await?,
+			};
+			if available.is_empty() {
+				return if self.buf.is_empty() { Ok(None) } else { self.take_line().map(Some) };
+			}
+
+			let limit = MAX_LINE_LENGTH - self.buf.len();
+			let (used, complete) = match available.iter().take(limit).position(|b| *b == b'\n') {
+				Some(newline) => (newline + 1, true),
+				None => (available.len().min(limit), false),
+			};
+			self.buf.extend_from_slice(&available[..used]);
+			self.reader.consume(used);
+
+			if complete {
+				return self.take_line().map(Some);
+			}
+			if self.buf.len() >= MAX_LINE_LENGTH {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"));
+			}
+			self.deadline.get_or_insert_with(|| Instant::now() + READ_TIMEOUT);
+		}
+	}
+
+	fn take_line(&mut self) -> io::Result<String> {
+		self.deadline = None;
+		String::from_utf8(std::mem::take(&mut self.buf))
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+}
+
+async fn handle_request<MD, BS>(
+	shared: &Shared<MD, BS>,
+	jobs: &mut Option<broadcast::Receiver<Job>>,
+	line: &str,
+) -> Response
+where
+	BS: BlockSubmit<Block>,
+{
+	let request = match serde_json::from_str::<Request>(line) {
+		Ok(request) => request,
+		Err(e) => return Response::error(Value::Null, Error::Parse(e).into()),
+	};
+
+	let result = match request.method.as_str() {
+		protocol::METHOD_SUBSCRIBE => {
+			*jobs = Some(shared.notifier.subscribe());
+			Ok(serde_json::to_value(shared.current.read().clone()).unwrap_or_default())
+		},
+		protocol::METHOD_SUBMIT => match serde_json::from_value::<Submit>(request.params) {
+			Ok(submit) => submit_job(shared, submit)
+				.await
+				.map(|result| serde_json::to_value(result).unwrap_or_default()),
+			Err(e) => Err(Error::InvalidParams(e)),
+		},
+		method => Err(Error::MethodNotFound(method.to_string())),
+	};
+
+	match result {
+		Ok(result) => Response::result(request.id, result),
+		Err(e) => Response::error(request.id, e.into()),
+	}
+}
+
+async fn submit_job<MD, BS>(
+	shared: &Shared<MD, BS>,
+	Submit { job_id, nonce }: Submit,
+) -> Result<BlockSubmitResult<Hash>, Error>
+where
+	BS: BlockSubmit<Block>,
+{
+	let block = shared
+		.jobs
+		.lock()
+		.get(&job_id)
+		.map(|miner_data| miner_data.block.clone())
+		.ok_or(Error::JobNotFound(job_id))?;
+	let seal = consensus::Seal { nonce }.encode();
+
+	shared
+		.block_submit
+		.submit_block(BlockSubmitParams { block, seal })
+		.await
+		.map_err(Error::BlockSubmit)
+}
+
+async fn next_job(
+	jobs: &mut Option<broadcast::Receiver<Job>>,
+) -> Result<Job, broadcast::error::RecvError> {
+	match jobs {
+		Some(jobs) => jobs.recv().await,
+		None => futures::future::pending().await,
+	}
+}
+
+fn to_line<T: serde::Serialize>(message: &T) -> String {
+	let mut line = serde_json::to_string(message).unwrap_or_default();
+	line.push('\n');
+	line
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use hashcash::{
+		client::miner::{traits::BlockSubmitError, ImportStatus},
+		primitives::core::{opaque::Header, BlockNumber},
+	};
+	use substrate::primitives::runtime::traits::Header as HeaderT;
+	use tokio::io::{AsyncBufRead, Lines};
+
+	#[derive(Default)]
+	struct MockBlockSubmit {
+		reject: bool,
+		submitted: Mutex<Vec<(Block, Vec<u8>)>>,
+	}
+
+	#[async_trait::async_trait]
+	impl BlockSubmit<Block> for MockBlockSubmit {
+		async fn submit_block(
+			&self,
+			params: BlockSubmitParams<Block>,
+		) -> Result<BlockSubmitResult<Hash>, BlockSubmitError> {
+			if self.reject {
+				return Err(BlockSubmitError::InvalidSeal);
+			}
+			let hash = params.block.hash();
+			self.submitted.lock().push((params.block, params.seal));
+			Ok(BlockSubmitResult { hash, status: ImportStatus::Best })
+		}
+	}
+
+	fn miner_data(number: BlockNumber) -> MinerData {
+		let header = Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		MinerData {
+			block: Block::new(header, vec![]),
+			difficulty: 100,
+			seed_hash: Hash::repeat_byte(1),
+		}
+	}
+
+	fn shared(block_submit: MockBlockSubmit) -> Shared<(), MockBlockSubmit> {
+		Shared::new((), block_submit, AccountId::new([0; 32]))
+	}
+
+	async fn request(
+		shared: &Shared<(), MockBlockSubmit>,
+		jobs: &mut Option<broadcast::Receiver<Job>>,
+		line: &str,
+	) -> Value {
+		serde_json::to_value(handle_request(shared, jobs, line).await).unwrap()
+	}
+
+	async fn read_message<R: AsyncBufRead + Unpin>(lines: &mut Lines<R>) -> Value {
+		serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+	}
+
+	#[tokio::test]
+	async fn malformed_requests_return_error_codes() {
+		let shared = shared(Default::default());
+		let mut jobs = None;
+
+		let response = request(&shared, &mut jobs, "{").await;
+		assert_eq!(response["error"]["code"], -32700);
+		assert_eq!(response["id"], Value::Null);
+
+		let response = request(&shared, &mut jobs, r#"{"id":1,"method":"mining.authorize"}"#).await;
+		assert_eq!(response["error"]["code"], -32601);
+		assert_eq!(response["id"], 1);
+
+		let line = r#"{"id":2,"method":"mining.submit","params":{"job_id":1}}"#;
+		assert_eq!(request(&shared, &mut jobs, line).await["error"]["code"], -32602);
+
+		let line = r#"{"id":3,"method":"mining.submit","params":[7,0]}"#;
+		assert_eq!(request(&shared, &mut jobs, line).await["error"]["code"], 1001);
+	}
+
+	#[tokio::test]
+	async fn subscribers_are_notified_of_new_jobs() {
+		let shared = shared(Default::default());
+		let mut jobs = None;
+
+		let subscribe = r#"{"id":1,"method":"mining.subscribe"}"#;
+		assert_eq!(request(&shared, &mut jobs, subscribe).await["result"], Value::Null);
+
+		let job = shared.update_job(miner_data(1)).unwrap();
+		assert_eq!(job.job_id, 1);
+		assert_eq!(job.pre_hash, miner_data(1).block.hash());
+		assert_eq!(jobs.as_mut().unwrap().recv().await.unwrap(), job);
+
+		// An unchanged template is not notified again.
+		assert_eq!(shared.update_job(miner_data(1)), None);
+
+		let job = shared.update_job(miner_data(2)).unwrap();
+		assert_eq!(job.job_id, 2);
+		assert_eq!(jobs.as_mut().unwrap().recv().await.unwrap(), job);
+		assert_eq!(
+			request(&shared, &mut jobs, subscribe).await["result"],
+			serde_json::to_value(&job).unwrap()
+		);
+	}
+
+	#[tokio::test]
+	async fn shares_are_submitted_as_sealed_blocks() {
+		let shared = shared(Default::default());
+		let mut jobs = None;
+		let job = shared.update_job(miner_data(1)).unwrap();
+
+		let line = format!(r#"{{"id":1,"method":"mining.submit","params":[{},42]}}"#, job.job_id);
+		let response = request(&shared, &mut jobs, &line).await;
+		assert_eq!(response["result"]["status"], "best");
+
+		let seal = consensus::Seal { nonce: 42 }.encode();
+		assert_eq!(*shared.block_submit.submitted.lock(), vec![(miner_data(1).block, seal)]);
+	}
+
+	#[tokio::test]
+	async fn rejected_shares_return_error_code() {
+		let shared = shared(MockBlockSubmit { reject: true, ..Default::default() });
+		let mut jobs = None;
+		let job = shared.update_job(miner_data(1)).unwrap();
+
+		let line = format!(
+			r#"{{"id":1,"method":"mining.submit","params":{{"job_id":{},"nonce":42}}}}"#,
+			job.job_id
+		);
+		assert_eq!(request(&shared, &mut jobs, &line).await["error"]["code"], 1002);
+	}
+
+	#[tokio::test]
+	async fn connections_receive_job_notifications() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let shared = Arc::new(shared(Default::default()));
+
+		let server = tokio::spawn({
+			let shared = shared.clone();
+			async move {
+				let (stream, peer) = listener.accept().await.unwrap();
+				handle_connection(shared, stream, peer).await;
+			}
+		});
+
+		let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+		let mut lines = BufReader::new(reader).lines();
+
+		writer.write_all(b"{\"id\":1,\"method\":\"mining.subscribe\"}\n").await.unwrap();
+		assert_eq!(read_message(&mut lines).await["result"], Value::Null);
+
+		let job = shared.update_job(miner_data(1)).unwrap();
+		let notification = read_message(&mut lines).await;
+		assert_eq!(notification["method"], protocol::METHOD_NOTIFY);
+		assert_eq!(notification["params"], serde_json::to_value(&job).unwrap());
+
+		drop(writer);
+		server.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn long_lines_are_rejected() {
+		let mut input = vec![b'a'; MAX_LINE_LENGTH - 1];
+		input.push(b'\n');
+		let line = LineReader::new(&input[..]).next_line().await.unwrap().unwrap();
+		assert_eq!(line.len(), MAX_LINE_LENGTH);
+
+		input.insert(0, b'a');
+		let e = LineReader::new(&input[..]).next_line().await.unwrap_err();
+		assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+	}
+
+	#[tokio::test]
+	async fn lines_are_read_across_writes() {
+		let (mut client, server) = tokio::io::duplex(64);
+		let mut lines = LineReader::new(server);
+
+		client.write_all(b"{\"id\"").await.unwrap();
+		client.write_all(b":1}\n{").await.unwrap();
+		assert_eq!(lines.next_line().await.unwrap().unwrap(), "{\"id\":1}\n");
+
+		client.write_all(b"}").await.unwrap();
+		drop(client);
+		assert_eq!(lines.next_line().await.unwrap().unwrap(), "{}");
+		assert_eq!(lines.next_line().await.unwrap(), None);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn incomplete_lines_time_out() {
+		let (mut client, server) = tokio::io::duplex(64);
+		let mut lines = LineReader::new(server);
+
+		client.write_all(b"{\"id\"").await.unwrap();
+		let e = lines.next_line().await.unwrap_err();
+		assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn unsubscribed_connections_are_closed() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let shared = Arc::new(shared(Default::default()));
+
+		let server = tokio::spawn(async move {
+			let (stream, peer) = listener.accept().await.unwrap();
+			handle_connection(shared, stream, peer).await;
+		});
+
+		let (reader, _writer) = TcpStream::connect(addr).await.unwrap().into_split();
+		let mut lines = BufReader::new(reader).lines();
+		assert_eq!(lines.next_line().await.unwrap(), None);
+		server.await.unwrap();
+	}
+
+	#[tokio::test]
+	async fn connections_beyond_limit_are_rejected() {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		let shared = Arc::new(shared(Default::default()));
+		let server = tokio::spawn(accept_connections(listener, shared, 1));
+
+		let subscribe = b"{\"id\":1,\"method\":\"mining.subscribe\"}\n";
+		let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+		let mut lines = BufReader::new(reader).lines();
+		writer.write_all(subscribe).await.unwrap();
+		assert_eq!(read_message(&mut lines).await["result"], Value::Null);
+
+		// The second miner is disconnected right away.
+		let (reader, mut writer) = TcpStream::connect(addr).await.unwrap().into_split();
+		let mut rejected = BufReader::new(reader).lines();
+		let _ = writer.write_all(subscribe).await;
+		assert!(!matches!(rejected.next_line().await, Ok(Some(_))));
+
+		server.abort();
+	}
+}
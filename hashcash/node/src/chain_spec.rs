@@ -4,12 +4,42 @@
 use crate::preludes::*;
 
 use hashcash::{
-	primitives::core::{AccountId, Difficulty},
+	primitives::core::{opaque::Block, AccountId, BlockNumber, Difficulty, Hash},
 	runtime::{RuntimeGenesisConfig, WASM_BINARY},
 };
-use substrate::client::service::{ChainType, GenericChainSpec, Properties};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use substrate::client::{
+	chain_spec::{get_extension, ChainSpecExtension},
+	consensus::pow::ChainLimits,
+	service::{self, ChainType, GenericChainSpec, Properties},
+};
+
+pub type ChainSpec = GenericChainSpec<RuntimeGenesisConfig, Extensions>;
+
+/// Node-specific chain spec extensions.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ChainSpecExtension)]
+#[serde(rename_all = "camelCase")]
+pub struct Extensions {
+	/// Hashes of the blocks every accepted chain must contain, by block number.
+	#[serde(default)]
+	pub checkpoints: BTreeMap<BlockNumber, Hash>,
+	/// Maximum number of best chain blocks a reorg can revert.
+	#[serde(default)]
+	pub max_reorg_depth: Option<BlockNumber>,
+}
+
+impl Extensions {
+	/// Returns the extensions of a chain spec, or the defaults if it has none.
+	pub fn from_chain_spec(chain_spec: &dyn service::ChainSpec) -> Self {
+		get_extension::<Self>(chain_spec.extensions()).cloned().unwrap_or_default()
+	}
 
-pub type ChainSpec = GenericChainSpec<RuntimeGenesisConfig>;
+	/// Returns the limits on chains accepted by block import.
+	pub fn chain_limits(&self) -> ChainLimits<Block> {
+		ChainLimits { checkpoints: self.checkpoints.clone(), max_reorg_depth: self.max_reorg_depth }
+	}
+}
 
 fn props() -> Properties {
 	let mut properties = Properties::new();
@@ -21,7 +51,7 @@ fn props() -> Properties {
 pub fn development_config() -> Result<ChainSpec, String> {
 	Ok(ChainSpec::builder(
 		WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?,
-		None,
+		Extensions::default(),
 	)
 	.with_name("Development")
 	.with_id("dev")
@@ -34,7 +64,7 @@ pub fn development_config() -> Result<ChainSpec, String> {
 pub fn local_testnet_config() -> Result<ChainSpec, String> {
 	Ok(ChainSpec::builder(
 		WASM_BINARY.ok_or_else(|| "Development wasm not available".to_string())?,
-		None,
+		Extensions::default(),
 	)
 	.with_name("Local Testnet")
 	.with_id("local_testnet")
@@ -55,3 +85,34 @@ fn testnet_genesis(
 		},
 	})
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn extensions_are_parsed() {
+		let extensions: Extensions = serde_json::from_str(&format!(
+			r#"{{"checkpoints": {{"10": "{:?}", "20": "{:?}"}}, "maxReorgDepth": 100}}"#,
+			Hash::repeat_byte(1),
+			Hash::repeat_byte(2),
+		))
+		.unwrap();
+
+		let limits = extensions.chain_limits();
+		assert_eq!(
+			limits.checkpoints,
+			[(10, Hash::repeat_byte(1)), (20, Hash::repeat_byte(2))].into()
+		);
+		assert_eq!(limits.max_reorg_depth, Some(100));
+	}
+
+	#[test]
+	fn missing_extensions_impose_no_limits() {
+		let extensions: Extensions = serde_json::from_str("{}").unwrap();
+
+		let limits = extensions.chain_limits();
+		assert!(limits.checkpoints.is_empty());
+		assert_eq!(limits.max_reorg_depth, None);
+	}
+}
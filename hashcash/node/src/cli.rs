@@ -4,6 +4,7 @@
 use crate::{chain_spec, preludes::*};
 
 use hashcash::primitives::core::AccountId;
+use std::net::SocketAddr;
 use substrate::{
 	client::cli::{self, commands::*, CliConfiguration, Error, SubstrateCli},
 	frames::benchmarking::cli::BenchmarkCmd,
@@ -15,9 +16,49 @@ pub struct CliOptions {
 	/// Specify the number of threads to use for mining.
 	#[arg(long, value_name = "COUNT")]
 	pub threads: Option<usize>,
+	/// Specify the number of threads to use for initializing a RandomX dataset.
+	///
+	/// Defaults to all available cores.
+	#[arg(long, value_name = "COUNT")]
+	pub randomx_init_threads: Option<usize>,
+	/// Maximum number of RandomX caches kept in memory.
+	#[arg(
+		long,
+		value_name = "COUNT",
+		default_value = "3",
+		value_parser = clap::value_parser!(u32).range(1..)
+	)]
+	pub randomx_caches: u32,
+	/// Maximum number of RandomX datasets kept in memory.
+	///
+	/// The dataset of the next epoch is only prepared in advance with at least two.
+	#[arg(
+		long,
+		value_name = "COUNT",
+		default_value = "2",
+		value_parser = clap::value_parser!(u32).range(1..)
+	)]
+	pub randomx_datasets: u32,
+	/// Maximum number of RandomX VMs to verify blocks.
+	///
+	/// Defaults to all available cores.
+	#[arg(long, value_name = "COUNT")]
+	pub randomx_verifier_vms: Option<usize>,
+	/// Verify blocks with light RandomX VMs only, which is slower but needs no dataset.
+	#[arg(long)]
+	pub randomx_light_mode: bool,
+	/// Disable JIT compilation of RandomX programs.
+	#[arg(long)]
+	pub randomx_disable_jit: bool,
+	/// Use software AES for RandomX even if hardware AES is available.
+	#[arg(long)]
+	pub randomx_soft_aes: bool,
 	/// Account for block mining rewards.
 	#[arg(long)]
 	pub author: Option<String>,
+	/// Specify the listen address of the Stratum mining server for remote miners.
+	#[arg(long, value_name = "ADDR")]
+	pub stratum_addr: Option<SocketAddr>,
 
 	// Hidden field to store a parsed author.
 	#[arg(long, hide(true))]
@@ -45,7 +86,7 @@ impl Cli {
 				let author = AccountId::from_string(author)
 					.map_err(|_| Error::Input("Invalid author".into()))?;
 				self.options.author_id = Some(author);
-			} else if self.run.role(is_dev)?.is_authority() {
+			} else if self.run.role(is_dev)?.is_authority() || self.options.stratum_addr.is_some() {
 				if let Some(keyring) = self.run.get_keyring() {
 					self.options.author_id = Some(keyring.to_account_id());
 				} else if is_dev {
@@ -3,11 +3,14 @@
 
 pub mod hashcash {
 	pub mod client {
+		pub use hashcash_client_api as api;
 		pub mod consensus {
 			pub use hashcash_client_consensus::*;
 		}
 		pub use hashcash_client_miner as miner;
+		pub use hashcash_client_randomx as randomx;
 		pub use hashcash_client_rpc as rpc;
+		pub use hashcash_client_stratum as stratum;
 	}
 	pub use hashcash_primitives as primitives;
 	pub use hashcash_runtime as runtime;
@@ -16,6 +19,7 @@ pub mod hashcash {
 pub mod substrate {
 	pub mod client {
 		pub use sc_basic_authorship as basic_authorship;
+		pub use sc_chain_spec as chain_spec;
 		pub use sc_cli as cli;
 		pub use sc_client_api as api;
 		pub mod consensus {
@@ -9,14 +9,26 @@ use hashcash::{
 			traits::{BlockSubmit, MinerDataBuilder},
 			MinerDataParams,
 		},
-		rpc::miner::{Miner, MinerApiServer},
+		rpc::{
+			coinbase::{Coinbase, CoinbaseApiServer},
+			difficulty::{DifficultyApiServer, DifficultyHistory},
+			miner::{Miner, MinerApiServer},
+			pow::{Pow, PowApiServer},
+		},
+	},
+	primitives::{
+		coinbase::{CoinbaseApi, EmissionApi},
+		core::{opaque::Block, AccountId, AccountNonce, Balance, BlockNumber, Difficulty, Moment},
+		difficulty::DifficultyHistoryApi,
 	},
-	primitives::core::{opaque::Block, AccountId, AccountNonce, Balance},
 };
 use jsonrpsee::RpcModule;
 use std::{error::Error, sync::Arc};
 use substrate::{
-	client::{rpc::api::DenyUnsafe, transaction_pool::api::TransactionPool},
+	client::{
+		api::BlockchainEvents, consensus::pow::ReorgNotifications, rpc::api::DenyUnsafe,
+		transaction_pool::api::TransactionPool,
+	},
 	frames::system::rpc::AccountNonceApi,
 	pallets::transaction_payment::rpc::TransactionPaymentRuntimeApi,
 	primitives::{
@@ -32,6 +44,7 @@ pub struct FullDeps<C, P, MD, BS> {
 	pub deny_unsafe: DenyUnsafe,
 	pub miner_data_builder: MD,
 	pub block_submit: BS,
+	pub reorgs: ReorgNotifications<Block>,
 }
 
 pub fn create_full<C, P, MD, BS>(
@@ -40,10 +53,14 @@ pub fn create_full<C, P, MD, BS>(
 where
 	C: ProvideRuntimeApi<Block>,
 	C: HeaderBackend<Block> + HeaderMetadata<Block, Error = BlockChainError> + 'static,
+	C: BlockchainEvents<Block>,
 	C: Send + Sync + 'static,
 	C::Api: AccountNonceApi<Block, AccountId, AccountNonce>,
 	C::Api: TransactionPaymentRuntimeApi<Block, Balance>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: DifficultyHistoryApi<Block, BlockNumber, Moment, Difficulty>,
+	C::Api: CoinbaseApi<Block, AccountId, BlockNumber, Balance>,
+	C::Api: EmissionApi<Block, BlockNumber, Balance>,
 	P: TransactionPool + 'static,
 	MD: MinerDataBuilder<Params = MinerDataParams> + Send + Sync + 'static,
 	BS: BlockSubmit<Block> + Send + Sync + 'static,
@@ -54,12 +71,15 @@ where
 	};
 
 	let mut module = RpcModule::new(());
-	let FullDeps { client, pool, deny_unsafe, miner_data_builder, block_submit } = deps;
+	let FullDeps { client, pool, deny_unsafe, miner_data_builder, block_submit, reorgs } = deps;
 
-	module.merge(System::new(client.clone(), pool, deny_unsafe).into_rpc())?;
+	module.merge(System::new(client.clone(), pool.clone(), deny_unsafe).into_rpc())?;
 	module.merge(TransactionPayment::new(client.clone()).into_rpc())?;
+	module.merge(DifficultyHistory::new(client.clone()).into_rpc())?;
+	module.merge(Coinbase::new(client.clone()).into_rpc())?;
 
-	module.merge(Miner::new(miner_data_builder, block_submit).into_rpc())?;
+	module.merge(Miner::new(client, pool, miner_data_builder, block_submit).into_rpc())?;
+	module.merge(Pow::new(reorgs).into_rpc())?;
 
 	Ok(module)
 }
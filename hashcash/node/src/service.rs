@@ -1,19 +1,25 @@
 // Copyright (c) Ryuichi Sakamoto
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use crate::{cli::CliOptions, preludes::*};
+use crate::{chain_spec, cli::CliOptions, preludes::*};
 
 use futures::FutureExt;
 use hashcash::{
 	client::{
+		api::timestamp::FutureTimeLimit,
 		consensus::RandomXAlgorithm,
 		miner::{
 			BlockSubmit, MinerDataBuilder, MinerDataBuilderParams, MiningWorker,
 			MiningWorkerBackend,
 		},
+		randomx,
+		stratum::{StratumParams, StratumServer},
 	},
 	primitives::{
-		coinbase::InherentDataProvider as CoinbaseInherentDataProvider,
+		coinbase::{
+			ErrorHandler as CoinbaseErrorHandler,
+			InherentDataProvider as CoinbaseInherentDataProvider,
+		},
 		core::{constants::SS58_PREFIX, opaque::Block, AccountId, Hash},
 	},
 	runtime::RuntimeApi,
@@ -28,9 +34,9 @@ use substrate::{
 		consensus::{
 			pow::{
 				EmptyPreRuntimeProvider, Error as PowError, ImportQueueParams, PowBlockImport,
-				PowParams, PreRuntimeProvider,
+				PowImportQueue, PowParams, PreRuntimeProvider,
 			},
-			DefaultImportQueue, LongestChain,
+			LongestChain,
 		},
 		executor::WasmExecutor,
 		network::config::FullNetworkConfiguration,
@@ -60,7 +66,7 @@ pub type Service = service::PartialComponents<
 	FullClient,
 	FullBackend,
 	FullSelectChain,
-	DefaultImportQueue<Block>,
+	PowImportQueue<Block>,
 	FullPool<Block, FullClient>,
 	(
 		PowBlockImport<
@@ -70,10 +76,14 @@ pub type Service = service::PartialComponents<
 			FullSelectChain,
 			RandomXAlgorithm<FullClient>,
 		>,
+		RandomXAlgorithm<FullClient>,
 		Option<Telemetry>,
 	),
 >;
 
+/// Maximum time a block timestamp may be ahead of the local clock, half of the block time.
+const FUTURE_TIME_LIMIT: Duration = Duration::from_secs(60);
+
 struct AuthorProvider {
 	pub author: AccountId,
 }
@@ -124,13 +134,19 @@ pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 		client.clone(),
 	);
 
-	let algorithm = RandomXAlgorithm::new(client.clone());
+	// The verifier is shared by block import, block submission and mining.
+	let verifier = randomx::Verifier::default();
+	let precompute_threads = verifier.max_vms();
+	let algorithm = RandomXAlgorithm::new(client.clone(), verifier);
 
+	let chain_limits = chain_spec::Extensions::from_chain_spec(&*config.chain_spec).chain_limits();
 	let pow_block_import = PowBlockImport::new(
 		client.clone(),
 		client.clone(),
 		select_chain.clone(),
 		algorithm.clone(),
+		chain_limits,
+		config.prometheus_registry(),
 	);
 
 	let import_queue = substrate::client::consensus::pow::import_queue(ImportQueueParams {
@@ -139,11 +155,11 @@ pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 		client: client.clone(),
 		algorithm: algorithm.clone(),
 		create_inherent_data_providers: move |_, ()| async move {
-			let timestamp = TimestampInherentDataProvider::from_system_time();
-			Ok(timestamp)
+			Ok((FutureTimeLimit::from_system_time(FUTURE_TIME_LIMIT), CoinbaseErrorHandler))
 		},
 		spawner: &task_manager.spawn_essential_handle(),
 		registry: config.prometheus_registry(),
+		precompute_threads,
 	})?;
 
 	Ok(service::PartialComponents {
@@ -154,11 +170,23 @@ pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (pow_block_import, telemetry),
+		other: (pow_block_import, algorithm, telemetry),
 	})
 }
 
 pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManager, Error> {
+	if let Some(threads) = options.randomx_init_threads {
+		randomx::set_init_threads(threads);
+	}
+	randomx::set_config(randomx::Config {
+		cache_count: options.randomx_caches,
+		dataset_count: options.randomx_datasets,
+		verifier_vms: options.randomx_verifier_vms,
+		light_mode: options.randomx_light_mode,
+		disable_jit: options.randomx_disable_jit,
+		soft_aes: options.randomx_soft_aes,
+	});
+
 	let service::PartialComponents {
 		client,
 		backend,
@@ -167,7 +195,7 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 		keystore_container,
 		select_chain,
 		transaction_pool,
-		other: (block_import, mut telemetry),
+		other: (block_import, algorithm, mut telemetry),
 	} = new_partial(&config)?;
 
 	let net_config = FullNetworkConfiguration::new(&config.network);
@@ -209,13 +237,11 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 	let role = config.role.clone();
 	let prometheus_registry = config.prometheus_registry().cloned();
 
-	let rpc_extensions_builder = {
-		let client = client.clone();
-		let pool = transaction_pool.clone();
-		let block_import = block_import.clone();
-		let sync_service = sync_service.clone();
-		let select_chain = select_chain.clone();
+	if let Some(registry) = prometheus_registry.as_ref() {
+		randomx::register_metrics(registry)?;
+	}
 
+	let remote_proposer_factory = {
 		let mut proposer_factory = ProposerFactory::new(
 			task_manager.spawn_handle(),
 			client.clone(),
@@ -225,28 +251,45 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 		);
 		proposer_factory.set_log_level(Level::Trace);
 
-		let proposer_factory = Arc::new(Mutex::new(proposer_factory));
+		Arc::new(Mutex::new(proposer_factory))
+	};
+
+	// The RPC and the Stratum server share the builder, so that both serve the same templates.
+	let miner_data_builder = Arc::new(MinerDataBuilder::new(MinerDataBuilderParams {
+		client: client.clone(),
+		create_inherent_data_providers: move |_, ()| async move {
+			Ok(TimestampInherentDataProvider::from_system_time())
+		},
+		pre_runtime_provider: EmptyPreRuntimeProvider::<Block>::new(),
+		proposer_factory: remote_proposer_factory,
+		select_chain: select_chain.clone(),
+		build_time: Duration::new(10, 0),
+	}));
+
+	let rpc_extensions_builder = {
+		let client = client.clone();
+		let pool = transaction_pool.clone();
+		let block_import = block_import.clone();
+		let reorgs = block_import.reorg_notifications();
+		let algorithm = algorithm.clone();
+		let sync_service = sync_service.clone();
+		let miner_data_builder = miner_data_builder.clone();
 
 		Box::new(move |deny_unsafe, _| {
-			let miner_data_builder = MinerDataBuilder::new(MinerDataBuilderParams {
-				client: client.clone(),
-				create_inherent_data_providers: move |_, ()| async move {
-					Ok(TimestampInherentDataProvider::from_system_time())
-				},
-				pre_runtime_provider: EmptyPreRuntimeProvider::<Block>::new(),
-				proposer_factory: proposer_factory.clone(),
-				select_chain: select_chain.clone(),
-				build_time: Duration::new(10, 0),
-			});
-			let block_submit =
-				BlockSubmit::new(client.clone(), block_import.clone(), sync_service.clone());
+			let block_submit = BlockSubmit::new(
+				client.clone(),
+				block_import.clone(),
+				algorithm.clone(),
+				sync_service.clone(),
+			);
 
 			let deps = crate::rpc::FullDeps {
 				client: client.clone(),
 				pool: pool.clone(),
 				deny_unsafe,
-				miner_data_builder,
+				miner_data_builder: miner_data_builder.clone(),
 				block_submit,
+				reorgs: reorgs.clone(),
 			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
@@ -267,8 +310,36 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 		telemetry: telemetry.as_mut(),
 	})?;
 
+	if let Some(listen_addr) = options.stratum_addr {
+		let author = options
+			.author_id
+			.clone()
+			.ok_or_else(|| Error::Other("The Stratum server requires an author".into()))?;
+		let block_submit = BlockSubmit::new(
+			client.clone(),
+			block_import.clone(),
+			algorithm.clone(),
+			sync_service.clone(),
+		);
+
+		let server = StratumServer::new(StratumParams {
+			client: client.clone(),
+			miner_data_builder,
+			block_submit,
+			sync_oracle: sync_service.clone(),
+			author,
+			listen_addr,
+			timeout: Duration::new(10, 0),
+		});
+		task_manager.spawn_handle().spawn("stratum", Some("mining"), server.run());
+	}
+
 	if role.is_authority() {
-		let algorithm = RandomXAlgorithm::new(client.clone());
+		task_manager.spawn_handle().spawn(
+			"randomx-prepare",
+			Some("mining"),
+			randomx::prepare_next_datasets(client.clone()),
+		);
 
 		let proposer_factory = ProposerFactory::new(
 			task_manager.spawn_handle(),
@@ -278,7 +349,14 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 			telemetry.as_ref().map(|x| x.handle()),
 		);
 
-		let author = options.author_id.clone().unwrap();
+		let author = options
+			.author_id
+			.clone()
+			.ok_or_else(|| Error::Other("Mining requires an author".into()))?;
+		info!(
+			"⚒️  Miner address is: {}",
+			author.to_ss58check_with_version(Ss58AddressFormat::custom(SS58_PREFIX))
+		);
 		let (mining_handle, mining_handle_task) =
 			substrate::client::consensus::pow::start_mining_worker(PowParams {
 				client: client.clone(),
@@ -307,12 +385,6 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 			mining_handle_task,
 		);
 
-		let author = options.author_id.unwrap();
-		info!(
-			"⚒️  Miner address is: {}",
-			author.to_ss58check_with_version(Ss58AddressFormat::custom(SS58_PREFIX))
-		);
-
 		let worker =
 			MiningWorker::new(MiningWorkerBackend::new(client.clone(), Arc::new(mining_handle)));
 		worker.start(options.threads.unwrap_or(1));
@@ -0,0 +1,82 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+//! Benchmarks for the coinbase pallet.
+
+use super::*;
+
+use frame_benchmarking::v2::*;
+use frame_support::traits::Hooks;
+use frame_system::{pallet_prelude::BlockNumberFor, RawOrigin};
+use sp_runtime::sp_std::prelude::*;
+
+const SEED: u32 = 0;
+
+/// Splits the block reward evenly among `n` new accounts.
+fn reward_splits<T: Config>(n: u32) -> Vec<(T::AccountId, BalanceOf<T>)> {
+	let reward = T::EmissionCurve::emit();
+	let share = reward / n.max(1).into();
+
+	let mut rewards = (0..n).map(|i| (account("miner", i, SEED), share)).collect::<Vec<_>>();
+	if let Some((_, last)) = rewards.last_mut() {
+		*last += reward - share * n.into();
+	}
+	rewards
+}
+
+#[benchmarks]
+mod benchmarks {
+	use super::*;
+
+	#[benchmark]
+	fn coinbase(n: Linear<1, { T::MaxRewardSplits::get() }>) {
+		// The genesis block has no block reward.
+		frame_system::Pallet::<T>::set_block_number(1u32.into());
+		let rewards = reward_splits::<T>(n);
+
+		#[extrinsic_call]
+		_(RawOrigin::None, rewards);
+
+		let height = frame_system::Pallet::<T>::block_number();
+		assert_eq!(Rewards::<T>::get(height).len(), n as usize);
+	}
+
+	#[benchmark]
+	fn on_initialize(n: Linear<0, { T::MaxRewardSplits::get() }>) -> Result<(), BenchmarkError> {
+		// The rewards of `n` accounts mature at the benchmarked block.
+		let height: BlockNumberFor<T> = 1u32.into();
+		frame_system::Pallet::<T>::set_block_number(height);
+		if n > 0 {
+			Pallet::<T>::coinbase(RawOrigin::None.into(), reward_splits::<T>(n))?;
+		}
+
+		#[block]
+		{
+			Pallet::<T>::on_initialize(height + T::MaturationTime::get());
+		}
+
+		assert!(Rewards::<T>::get(height).is_empty());
+		Ok(())
+	}
+
+	#[benchmark]
+	fn on_finalize(n: Linear<0, { T::MaxRewardSplits::get() }>) -> Result<(), BenchmarkError> {
+		// The fees of the block are credited to `n` accounts, or burned without any.
+		let height: BlockNumberFor<T> = 1u32.into();
+		frame_system::Pallet::<T>::set_block_number(height);
+		if n > 0 {
+			Pallet::<T>::coinbase(RawOrigin::None.into(), reward_splits::<T>(n))?;
+		}
+		PendingFees::<T>::put(T::EmissionCurve::emit());
+
+		#[block]
+		{
+			Pallet::<T>::on_finalize(height);
+		}
+
+		assert!(PendingFees::<T>::get().is_zero());
+		Ok(())
+	}
+
+	impl_benchmark_test_suite!(Pallet, crate::mock::new_test_ext(), crate::mock::Test);
+}
@@ -0,0 +1,140 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+//! Emission curves depending only on the block number.
+//!
+//! Block `0` is the genesis block, which has no block reward.
+
+use crate::{BalanceOf, Config, EmissionCurve};
+
+use frame_system::pallet_prelude::BlockNumberFor;
+use sp_runtime::{
+	sp_std::marker::PhantomData,
+	traits::{Get, One, SaturatedConversion, Saturating, Zero},
+	PerThing, Perquintill,
+};
+
+/// Smooth exponential decay, emitting the fraction `Decay` of the remaining supply in every block.
+///
+/// The supply remaining after block `h` is `Supply * (1 - Decay)^h`. `Supply` only covers block
+/// rewards, so the total issuance converges to `Supply` plus any balances endowed at genesis.
+pub struct ExponentialDecay<T, Supply, Decay>(PhantomData<(T, Supply, Decay)>);
+
+impl<T, Supply, Decay> ExponentialDecay<T, Supply, Decay>
+where
+	T: Config,
+	Supply: Get<BalanceOf<T>>,
+	Decay: Get<Perquintill>,
+{
+	/// Returns the supply remaining after the block at `height`.
+	fn remaining(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		Decay::get()
+			.left_from_one()
+			.saturating_pow(height.saturated_into())
+			.mul_floor(Supply::get())
+	}
+}
+
+impl<T, Supply, Decay> EmissionCurve<T> for ExponentialDecay<T, Supply, Decay>
+where
+	T: Config,
+	Supply: Get<BalanceOf<T>>,
+	Decay: Get<Perquintill>,
+{
+	fn reward(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		if height.is_zero() {
+			return Zero::zero()
+		}
+		Self::remaining(height - One::one()).saturating_sub(Self::remaining(height))
+	}
+
+	fn issued(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		Supply::get().saturating_sub(Self::remaining(height))
+	}
+}
+
+/// Bitcoin-style halvings of `InitialReward` every `Interval` blocks.
+pub struct Halving<T, InitialReward, Interval>(PhantomData<(T, InitialReward, Interval)>);
+
+impl<T, InitialReward, Interval> Halving<T, InitialReward, Interval>
+where
+	T: Config,
+	Interval: Get<BlockNumberFor<T>>,
+{
+	fn interval() -> u128 {
+		Interval::get().saturated_into::<u128>().max(1)
+	}
+}
+
+impl<T, InitialReward, Interval> EmissionCurve<T> for Halving<T, InitialReward, Interval>
+where
+	T: Config,
+	InitialReward: Get<BalanceOf<T>>,
+	Interval: Get<BlockNumberFor<T>>,
+{
+	fn reward(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		if height.is_zero() {
+			return Zero::zero()
+		}
+		let halvings = (height.saturated_into::<u128>() - 1) / Self::interval();
+		let initial_reward: u128 = InitialReward::get().saturated_into();
+		initial_reward
+			.checked_shr(halvings.saturated_into())
+			.unwrap_or(0)
+			.saturated_into()
+	}
+
+	fn issued(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		let height: u128 = height.saturated_into();
+		let interval = Self::interval();
+		let initial_reward: u128 = InitialReward::get().saturated_into();
+
+		// The reward is zero after 128 halvings.
+		let halvings = (height / interval).min(128) as u32;
+		let mut issued = (0..halvings).fold(0u128, |acc, halving| {
+			acc.saturating_add((initial_reward >> halving).saturating_mul(interval))
+		});
+		if halvings < 128 {
+			issued = issued.saturating_add((initial_reward >> halvings) * (height % interval));
+		}
+		issued.saturated_into()
+	}
+}
+
+/// Monero-style tail emission, keeping the block reward of `Curve` at least `Floor`.
+///
+/// The block reward of `Curve` must never increase.
+pub struct TailEmission<T, Curve, Floor>(PhantomData<(T, Curve, Floor)>);
+
+impl<T, Curve, Floor> EmissionCurve<T> for TailEmission<T, Curve, Floor>
+where
+	T: Config,
+	Curve: EmissionCurve<T>,
+	Floor: Get<BalanceOf<T>>,
+{
+	fn reward(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		if height.is_zero() {
+			return Zero::zero()
+		}
+		Curve::reward(height).max(Floor::get())
+	}
+
+	fn issued(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		let floor = Floor::get();
+
+		// Binary search for the first block whose reward of `Curve` is below the floor.
+		let (mut low, mut high) = (BlockNumberFor::<T>::one(), height.saturating_add(One::one()));
+		while low < high {
+			let mid = low + (high - low) / 2u32.into();
+			if Curve::reward(mid) < floor {
+				high = mid;
+			} else {
+				low = mid + One::one();
+			}
+		}
+
+		let head = low - One::one();
+		let tail: BalanceOf<T> = (height - head).saturated_into::<u128>().saturated_into();
+		Curve::issued(head).saturating_add(floor.saturating_mul(tail))
+	}
+}
@@ -4,29 +4,60 @@
 //! Coinbase pallet for block rewards.
 
 #![cfg_attr(not(feature = "std"), no_std)]
-// XXX: Suppress deprecation warnings for constant weight of coinbase call.
-// Remove this line when the weight is properly implemented.
-#![allow(deprecated)]
+
+#[cfg(feature = "runtime-benchmarks")]
+mod benchmarking;
+pub mod emission;
+#[cfg(test)]
+mod mock;
+#[cfg(test)]
+mod tests;
+pub mod weights;
 
 pub use pallet::*;
+pub use weights::WeightInfo;
 
-use frame_support::traits::{Currency, LockIdentifier, LockableCurrency, WithdrawReasons};
-use hashcash_primitives::coinbase::{InherentError, InherentType, INHERENT_IDENTIFIER};
+use frame_support::traits::{
+	Currency, Imbalance, LockIdentifier, LockableCurrency, OnUnbalanced, WithdrawReasons,
+};
+use frame_system::pallet_prelude::BlockNumberFor;
+use hashcash_primitives::coinbase::{
+	validate_shares, InherentError, InherentType, LockedRewards, INHERENT_IDENTIFIER,
+};
 use parity_scale_codec::FullCodec;
 use sp_inherents::{InherentData, InherentIdentifier};
-use sp_runtime::traits::{AtLeast32BitUnsigned, Get, SaturatedConversion, Zero};
+use sp_runtime::{
+	helpers_128bit::multiply_by_rational_with_rounding,
+	sp_std::{collections::btree_set::BTreeSet, marker::PhantomData, vec::Vec},
+	traits::{AtLeast32BitUnsigned, Get, One, SaturatedConversion, Saturating, Zero},
+	Rounding,
+};
 
 pub type BalanceOf<T> =
 	<<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+pub type NegativeImbalanceOf<T> = <<T as Config>::Currency as Currency<
+	<T as frame_system::Config>::AccountId,
+>>::NegativeImbalance;
 pub type InherentTypeOf<T> =
 	InherentType<<T as frame_system::Config>::AccountId, <T as Config>::Difficulty>;
 
 const LOG_TARGET: &str = "runtime::coinbase";
 const LOCK_IDENTIFIER: LockIdentifier = *b"coinbase";
 
+/// Schedule of block rewards.
 ///
+/// See [`emission`] for the provided curves.
 pub trait EmissionCurve<T: Config> {
-	fn emit() -> BalanceOf<T>;
+	/// Returns the block reward of the block at `height`.
+	fn reward(height: BlockNumberFor<T>) -> BalanceOf<T>;
+
+	/// Returns the sum of the block rewards up to and including the block at `height`.
+	fn issued(height: BlockNumberFor<T>) -> BalanceOf<T>;
+
+	/// Returns the block reward of the current block.
+	fn emit() -> BalanceOf<T> {
+		Self::reward(frame_system::Pallet::<T>::block_number())
+	}
 }
 
 #[frame_support::pallet]
@@ -41,6 +72,8 @@ pub mod pallet {
 
 	#[pallet::config]
 	pub trait Config: frame_system::Config {
+		/// The overarching event type.
+		type RuntimeEvent: From<Event<Self>> + IsType<<Self as frame_system::Config>::RuntimeEvent>;
 		///
 		type EmissionCurve: EmissionCurve<Self>;
 		///
@@ -51,6 +84,8 @@ pub mod pallet {
 		type MaturationTime: Get<BlockNumberFor<Self>>;
 		///
 		type Difficulty: FullCodec + Copy + AtLeast32BitUnsigned;
+		/// Weight information for the extrinsics and hooks of this pallet.
+		type WeightInfo: WeightInfo;
 	}
 
 	#[pallet::storage]
@@ -67,6 +102,19 @@ pub mod pallet {
 	#[pallet::getter(fn reward_locks)]
 	pub type RewardLocks<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, BalanceOf<T>>;
 
+	/// Fees of the current block, credited to its reward splits when the block is finalized.
+	#[pallet::storage]
+	pub type PendingFees<T: Config> = StorageValue<_, BalanceOf<T>, ValueQuery>;
+
+	#[pallet::event]
+	#[pallet::generate_deposit(pub(super) fn deposit_event)]
+	pub enum Event<T: Config> {
+		/// The block reward was issued to the reward splits, excluding fees credited later.
+		RewardIssued { block: BlockNumberFor<T>, splits: Vec<(T::AccountId, BalanceOf<T>)> },
+		/// A locked reward matured and was unlocked.
+		RewardMatured { account: T::AccountId, amount: BalanceOf<T> },
+	}
+
 	#[pallet::error]
 	pub enum Error<T> {
 		TooManyRewardSplits,
@@ -75,8 +123,15 @@ pub mod pallet {
 
 	#[pallet::call]
 	impl<T: Config> Pallet<T> {
+		/// Issues the block reward to `rewards`.
+		///
+		/// The weight includes crediting the fees of the block to the splits in `on_finalize`.
 		#[pallet::call_index(0)]
-		#[pallet::weight(0)]
+		#[pallet::weight((
+			T::WeightInfo::coinbase(rewards.len() as u32)
+				.saturating_add(T::WeightInfo::on_finalize(rewards.len() as u32)),
+			DispatchClass::Mandatory,
+		))]
 		pub fn coinbase(
 			origin: OriginFor<T>,
 			rewards: Vec<(T::AccountId, BalanceOf<T>)>,
@@ -87,30 +142,25 @@ pub mod pallet {
 				Error::<T>::TooManyRewardSplits
 			);
 
+			ensure!(
+				rewards.iter().all(|(_, value)| !value.is_zero()) &&
+					Self::splits_are_distinct(&rewards),
+				Error::<T>::InvalidReward
+			);
+
 			let reward_emitted = T::EmissionCurve::emit();
 			let mut reward_given = BalanceOf::<T>::zero();
 			for (dest, value) in &rewards {
 				drop(T::Currency::deposit_creating(dest, *value));
 				reward_given += *value;
-
-				RewardLocks::<T>::mutate(dest, |lock| {
-					let new_lock = match lock.take() {
-						Some(lock) => lock + *value,
-						None => *value,
-					};
-					T::Currency::set_lock(
-						LOCK_IDENTIFIER,
-						dest,
-						new_lock,
-						WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
-					);
-					*lock = Some(new_lock);
-				});
+				Self::lock_reward(dest, *value);
 			}
 			ensure!(reward_given == reward_emitted, Error::<T>::InvalidReward);
 
+			let block = frame_system::Pallet::<T>::block_number();
+			Self::deposit_event(Event::RewardIssued { block, splits: rewards.clone() });
 			Rewards::<T>::insert(
-				frame_system::Pallet::<T>::block_number(),
+				block,
 				BoundedVec::<_, T::MaxRewardSplits>::try_from(rewards).unwrap(),
 			);
 
@@ -121,29 +171,40 @@ pub mod pallet {
 	#[pallet::hooks]
 	impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
 		fn on_initialize(height: BlockNumberFor<T>) -> Weight {
-			if height > T::MaturationTime::get() {
-				let unlocked_height = height - T::MaturationTime::get();
-
-				for (dest, value) in Rewards::<T>::take(unlocked_height) {
-					RewardLocks::<T>::mutate(&dest, |lock| {
-						let locked = lock.unwrap();
-						if locked > value {
-							T::Currency::set_lock(
-								LOCK_IDENTIFIER,
-								&dest,
-								locked - value,
-								WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
-							);
-							*lock = Some(locked - value);
-						} else {
-							T::Currency::remove_lock(LOCK_IDENTIFIER, &dest);
-							*lock = None;
-						}
-					});
-				}
+			// Crediting fees to the splits is charged by the coinbase, which knows their number.
+			let fees_weight = T::WeightInfo::on_finalize(0);
+			if height <= T::MaturationTime::get() {
+				return T::WeightInfo::on_initialize(0).saturating_add(fees_weight)
 			}
+			let unlocked_height = height - T::MaturationTime::get();
+			let rewards = Rewards::<T>::take(unlocked_height);
+			let weight =
+				T::WeightInfo::on_initialize(rewards.len() as u32).saturating_add(fees_weight);
 
-			Weight::from_parts(0, 0)
+			for (dest, value) in rewards {
+				RewardLocks::<T>::mutate(&dest, |lock| {
+					let locked = lock.unwrap();
+					if locked > value {
+						T::Currency::set_lock(
+							LOCK_IDENTIFIER,
+							&dest,
+							locked - value,
+							WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
+						);
+						*lock = Some(locked - value);
+					} else {
+						T::Currency::remove_lock(LOCK_IDENTIFIER, &dest);
+						*lock = None;
+					}
+				});
+				Self::deposit_event(Event::RewardMatured { account: dest, amount: value });
+			}
+
+			weight
+		}
+
+		fn on_finalize(height: BlockNumberFor<T>) {
+			Self::distribute_fees(height);
 		}
 	}
 
@@ -153,38 +214,93 @@ pub mod pallet {
 		type Error = InherentError;
 		const INHERENT_IDENTIFIER: InherentIdentifier = INHERENT_IDENTIFIER;
 
+		/// Creates the coinbase from the share data, which fails only if the data is missing or
+		/// rejected by [`validate_shares`].
 		fn create_inherent(data: &InherentData) -> Option<Self::Call> {
-			let inherent_data = data
-				.get_data::<InherentTypeOf<T>>(&INHERENT_IDENTIFIER)
-				.expect("Coinbase inherent data not correctly encoded")
-				.expect("Coinbase inherent data must be provided");
+			let height = frame_system::Pallet::<T>::block_number();
+			let shares = match data.get_data::<InherentTypeOf<T>>(&INHERENT_IDENTIFIER) {
+				Ok(Some(shares)) => shares,
+				Ok(None) => {
+					log::error!(
+						target: LOG_TARGET,
+						"Coinbase share data missing: height={:?}",
+						height,
+					);
+					return None
+				},
+				Err(e) => {
+					log::error!(
+						target: LOG_TARGET,
+						"Coinbase share data malformed: height={:?} error={:?}",
+						height,
+						e,
+					);
+					return None
+				},
+			};
 
-			let total_weight: u128 = inherent_data
-				.iter()
-				.fold(0u128, |acc, (_, weight)| acc + (*weight).saturated_into::<u128>());
-			let reward: u128 = T::EmissionCurve::emit().saturated_into();
+			match Self::reward_splits(shares, T::EmissionCurve::emit()) {
+				Ok(rewards) => Some(Call::coinbase { rewards }),
+				Err(e) => {
+					log::error!(
+						target: LOG_TARGET,
+						"Coinbase not created: height={:?} error={:?}",
+						height,
+						e,
+					);
+					None
+				},
+			}
+		}
 
-			let mut rewards = Vec::<(T::AccountId, BalanceOf<T>)>::new();
-			let mut reward_given = 0u128;
-			let mut cumulative_weight = 0u128;
+		/// Checks the coinbase at the state of the parent block.
+		///
+		/// Imported blocks come without share data, so only the number of splits, their values and
+		/// accounts, and their sum are checked. Whether the splits follow the work of the shares
+		/// is only checked by the author of the block.
+		fn check_inherent(call: &Self::Call, data: &InherentData) -> Result<(), Self::Error> {
+			let Call::coinbase { rewards } = call else { return Ok(()) };
 
-			for (dest, weight) in inherent_data {
-				cumulative_weight += weight.saturated_into::<u128>();
-				let next_value = cumulative_weight * reward / total_weight;
-				rewards.push((dest, (next_value - reward_given).saturated_into()));
-				reward_given = next_value;
+			let max = T::MaxRewardSplits::get();
+			if rewards.len() > max as usize {
+				return Err(InherentError::TooManySplits { splits: rewards.len() as u32, max })
+			}
+			if rewards.iter().any(|(_, value)| value.is_zero()) {
+				return Err(InherentError::ZeroSplit)
+			}
+			if !Self::splits_are_distinct(rewards) {
+				return Err(InherentError::DuplicateSplit)
 			}
 
-			if rewards
+			let height = frame_system::Pallet::<T>::block_number() + One::one();
+			let reward = T::EmissionCurve::reward(height);
+			let expected: u128 = reward.saturated_into();
+			let actual = rewards
 				.iter()
-				.fold(0u128, |acc, (_, reward)| acc + (*reward).saturated_into::<u128>()) !=
-				reward
-			{
-				log::error!(target: LOG_TARGET, "Sum of reward splits is not equal to the total reward");
-				return None
+				.fold(0u128, |acc, (_, value)| acc.saturating_add((*value).saturated_into()));
+			if actual != expected {
+				return Err(InherentError::SumMismatch { expected, actual })
+			}
+
+			// Share data is only known to the author of the block.
+			let shares = match data.get_data::<InherentTypeOf<T>>(&INHERENT_IDENTIFIER) {
+				Ok(Some(shares)) => shares,
+				Ok(None) => return Ok(()),
+				Err(_) => return Err(InherentError::InvalidShareData),
+			};
+			if *rewards != Self::reward_splits(shares, reward)? {
+				return Err(InherentError::SplitMismatch)
 			}
 
-			Some(Call::coinbase { rewards })
+			Ok(())
+		}
+
+		/// Every block requires a coinbase, even if its reward is zero.
+		///
+		/// `create_inherent` only fails if the share data is missing or invalid, which the inherent
+		/// data provider of the node rules out.
+		fn is_inherent_required(_: &InherentData) -> Result<Option<Self::Error>, Self::Error> {
+			Ok(Some(InherentError::MissingCoinbase))
 		}
 
 		fn is_inherent(call: &Self::Call) -> bool {
@@ -192,3 +308,211 @@ pub mod pallet {
 		}
 	}
 }
+
+impl<T: Config> Pallet<T> {
+	/// Splits `reward` among `shares` in proportion to their weights.
+	///
+	/// The share data is checked with [`validate_shares`], as the inherent data provider of the
+	/// node does. Shares too small to earn any of `reward` get no split.
+	///
+	/// If there are more shares than `MaxRewardSplits`, only the largest shares get a split, which
+	/// bounds the weight of the coinbase. The part of `reward` earned by the smallest shares is
+	/// spread over the kept splits in proportion to their weights, so no single account is paid for
+	/// the work of others. Share data is expected to fit the bound, e.g. by paying small miners of
+	/// a pool out of band.
+	pub fn reward_splits(
+		shares: InherentTypeOf<T>,
+		reward: BalanceOf<T>,
+	) -> Result<Vec<(T::AccountId, BalanceOf<T>)>, InherentError> {
+		validate_shares(&shares)?;
+
+		let mut shares = shares.into_iter().collect::<Vec<_>>();
+		let max = T::MaxRewardSplits::get().max(1) as usize;
+		if shares.len() > max {
+			shares.sort_by(|(a, x), (b, y)| y.cmp(x).then_with(|| a.cmp(b)));
+			log::debug!(
+				target: LOG_TARGET,
+				"Dropped smallest coinbase shares: dropped={} max={}",
+				shares.len() - max,
+				max,
+			);
+			shares.truncate(max);
+		}
+
+		let reward: u128 = reward.saturated_into();
+		let total_weight = shares
+			.iter()
+			.map(|(_, weight)| weight)
+			.fold(0u128, |acc, weight| acc.saturating_add((*weight).saturated_into()));
+
+		let mut rewards = Vec::with_capacity(shares.len());
+		let mut reward_given = 0u128;
+		let mut cumulative_weight = 0u128;
+
+		for (dest, weight) in shares {
+			cumulative_weight = cumulative_weight.saturating_add(weight.saturated_into());
+			let next_value = multiply_by_rational_with_rounding(
+				reward,
+				cumulative_weight,
+				total_weight,
+				Rounding::Down,
+			)
+			.unwrap_or(reward);
+			if next_value > reward_given {
+				rewards.push((dest, (next_value - reward_given).saturated_into()));
+			}
+			reward_given = next_value;
+		}
+
+		Ok(rewards)
+	}
+
+	/// Returns whether every account has at most one reward split.
+	fn splits_are_distinct(rewards: &[(T::AccountId, BalanceOf<T>)]) -> bool {
+		let mut accounts = BTreeSet::new();
+		rewards.iter().all(|(dest, _)| accounts.insert(dest))
+	}
+
+	/// Returns the block reward of the block at `height`.
+	pub fn block_reward(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		T::EmissionCurve::reward(height)
+	}
+
+	/// Returns the total issuance projected at block `height` by the emission curve.
+	///
+	/// Only block rewards are projected, as the fees burned in other blocks are unknown.
+	pub fn projected_issuance(height: BlockNumberFor<T>) -> BalanceOf<T> {
+		let now = frame_system::Pallet::<T>::block_number();
+		let total_issuance = T::Currency::total_issuance();
+		if height >= now {
+			let scheduled =
+				T::EmissionCurve::issued(height).saturating_sub(T::EmissionCurve::issued(now));
+			total_issuance.saturating_add(scheduled)
+		} else {
+			let issued =
+				T::EmissionCurve::issued(now).saturating_sub(T::EmissionCurve::issued(height));
+			total_issuance.saturating_sub(issued)
+		}
+	}
+
+	/// Returns the locked rewards of `account` with the heights at which they unlock.
+	pub fn locked_rewards(
+		account: &T::AccountId,
+	) -> LockedRewards<BlockNumberFor<T>, BalanceOf<T>> {
+		let now = frame_system::Pallet::<T>::block_number();
+		let maturation_time = T::MaturationTime::get();
+
+		// Rewards of the blocks up to `now - maturation_time` are already unlocked.
+		let mut height = now.saturating_sub(maturation_time) + One::one();
+		let mut maturing = Vec::new();
+		while height <= now {
+			let amount = Rewards::<T>::get(height)
+				.iter()
+				.filter(|(dest, _)| dest == account)
+				.fold(BalanceOf::<T>::zero(), |acc, (_, value)| acc.saturating_add(*value));
+			if !amount.is_zero() {
+				maturing.push((height + maturation_time, amount));
+			}
+			height += One::one();
+		}
+
+		LockedRewards { locked: RewardLocks::<T>::get(account).unwrap_or_default(), maturing }
+	}
+
+	/// Locks a reward of the current block until it matures.
+	fn lock_reward(dest: &T::AccountId, value: BalanceOf<T>) {
+		RewardLocks::<T>::mutate(dest, |lock| {
+			let new_lock = match lock.take() {
+				Some(lock) => lock + value,
+				None => value,
+			};
+			T::Currency::set_lock(
+				LOCK_IDENTIFIER,
+				dest,
+				new_lock,
+				WithdrawReasons::except(WithdrawReasons::TRANSACTION_PAYMENT),
+			);
+			*lock = Some(new_lock);
+		});
+	}
+
+	/// Sets aside `fees` to be credited to the reward splits of the current block when it is
+	/// finalized.
+	///
+	/// The fees are burned now and issued again by [`Self::distribute_fees`], so that every
+	/// transaction only pays for a single storage write.
+	pub fn credit_fees(fees: NegativeImbalanceOf<T>) {
+		PendingFees::<T>::mutate(|pending| *pending = pending.saturating_add(fees.peek()));
+		drop(fees);
+	}
+
+	/// Credits the pending fees to the reward splits of the block at `height` in proportion to
+	/// their rewards, locked until the rewards mature.
+	///
+	/// The fees stay burned if the block has no reward splits.
+	fn distribute_fees(height: BlockNumberFor<T>) {
+		let amount = PendingFees::<T>::take();
+		if amount.is_zero() {
+			return
+		}
+
+		Rewards::<T>::mutate(height, |rewards| {
+			if rewards.is_empty() {
+				log::debug!(target: LOG_TARGET, "No reward splits at #{:?}, burning fees", height);
+				return
+			}
+
+			let fees = T::Currency::issue(amount);
+			let amount: u128 = amount.saturated_into();
+			let total_reward = rewards
+				.iter()
+				.fold(0u128, |acc, (_, value)| acc.saturating_add((*value).saturated_into()));
+			// Splits share the fees equally if the block reward is zero.
+			let weight_of = |value: BalanceOf<T>| match total_reward {
+				0 => 1,
+				_ => value.saturated_into::<u128>(),
+			};
+			let total_weight = match total_reward {
+				0 => rewards.len() as u128,
+				_ => total_reward,
+			};
+
+			let mut remaining = fees;
+			let mut credited = 0u128;
+			let mut cumulative_weight = 0u128;
+
+			for (dest, value) in rewards.iter_mut() {
+				cumulative_weight += weight_of(*value);
+				let next_credited = multiply_by_rational_with_rounding(
+					amount,
+					cumulative_weight,
+					total_weight,
+					Rounding::Down,
+				)
+				.unwrap_or(amount);
+				let (share, rest) = remaining.split((next_credited - credited).saturated_into());
+				remaining = rest;
+				credited = next_credited;
+
+				// Fees which cannot be deposited, e.g. below the existential deposit, are burned.
+				let deposited = T::Currency::deposit_creating(dest, share.peek());
+				let deposited_value = deposited.peek();
+				drop(share.offset(deposited));
+
+				if !deposited_value.is_zero() {
+					*value += deposited_value;
+					Self::lock_reward(dest, deposited_value);
+				}
+			}
+		});
+	}
+}
+
+/// Handler of transaction fees and tips, which credits them to the reward splits of the block.
+pub struct DealWithFees<T>(PhantomData<T>);
+
+impl<T: Config> OnUnbalanced<NegativeImbalanceOf<T>> for DealWithFees<T> {
+	fn on_nonzero_unbalanced(amount: NegativeImbalanceOf<T>) {
+		Pallet::<T>::credit_fees(amount);
+	}
+}
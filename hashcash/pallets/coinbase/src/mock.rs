@@ -0,0 +1,73 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{self as pallet_coinbase, EmissionCurve};
+
+use frame_support::{
+	derive_impl,
+	traits::{ConstU32, ConstU64},
+};
+use sp_runtime::BuildStorage;
+
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+	pub enum Test {
+		System: frame_system,
+		Balances: pallet_balances,
+		Coinbase: pallet_coinbase,
+	}
+);
+
+#[derive_impl(frame_system::config_preludes::TestDefaultConfig as frame_system::DefaultConfig)]
+impl frame_system::Config for Test {
+	type Block = Block;
+	type AccountData = pallet_balances::AccountData<u64>;
+}
+
+#[derive_impl(pallet_balances::config_preludes::TestDefaultConfig as pallet_balances::DefaultConfig)]
+impl pallet_balances::Config for Test {
+	type AccountStore = System;
+}
+
+/// Block reward of the mock runtime.
+pub const EMISSION: u64 = 1000;
+/// Account paying transaction fees.
+pub const PAYER: u64 = 100;
+
+pub struct FixedEmission;
+
+impl EmissionCurve<Test> for FixedEmission {
+	fn reward(height: u64) -> u64 {
+		if height == 0 {
+			0
+		} else {
+			EMISSION
+		}
+	}
+
+	fn issued(height: u64) -> u64 {
+		height * EMISSION
+	}
+}
+
+impl pallet_coinbase::Config for Test {
+	type RuntimeEvent = RuntimeEvent;
+	type EmissionCurve = FixedEmission;
+	type Currency = Balances;
+	type MaxRewardSplits = ConstU32<4>;
+	type MaturationTime = ConstU64<2>;
+	type Difficulty = u64;
+	type WeightInfo = ();
+}
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+	let mut storage = frame_system::GenesisConfig::<Test>::default().build_storage().unwrap();
+	pallet_balances::GenesisConfig::<Test> { balances: vec![(PAYER, 1_000_000)] }
+		.assimilate_storage(&mut storage)
+		.unwrap();
+
+	let mut ext = sp_io::TestExternalities::new(storage);
+	ext.execute_with(|| System::set_block_number(1));
+	ext
+}
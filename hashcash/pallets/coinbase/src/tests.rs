@@ -0,0 +1,409 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{emission::*, mock::*, *};
+
+use frame_support::{
+	assert_noop, assert_ok,
+	dispatch::GetDispatchInfo,
+	inherent::ProvideInherent,
+	parameter_types,
+	traits::{ExistenceRequirement, Hooks, UnfilteredDispatchable},
+};
+use frame_system::RawOrigin;
+use sp_runtime::Perquintill;
+
+parameter_types! {
+	pub const Supply: u64 = 1_000_000_000;
+	pub Decay: Perquintill = Perquintill::from_percent(1);
+	pub const InitialReward: u64 = 50;
+	pub const Interval: u64 = 10;
+	pub const Floor: u64 = 3;
+}
+
+type Decaying = ExponentialDecay<Test, Supply, Decay>;
+type Halvings = Halving<Test, InitialReward, Interval>;
+type Tail = TailEmission<Test, Halvings, Floor>;
+
+fn share_data(shares: &[(u64, u64)]) -> InherentData {
+	let mut data = InherentData::new();
+	data.put_data(INHERENT_IDENTIFIER, &shares.iter().copied().collect::<InherentTypeOf<Test>>())
+		.unwrap();
+	data
+}
+
+/// Checks that `issued` is the sum of the block rewards.
+fn assert_issued_is_sum<C: EmissionCurve<Test>>(blocks: u64) {
+	let mut issued = 0;
+	for height in 0..=blocks {
+		issued += C::reward(height);
+		assert_eq!(C::issued(height), issued, "height {}", height);
+	}
+}
+
+fn withdraw_fee(amount: u64) -> NegativeImbalanceOf<Test> {
+	Balances::withdraw(
+		&PAYER,
+		amount,
+		WithdrawReasons::TRANSACTION_PAYMENT,
+		ExistenceRequirement::KeepAlive,
+	)
+	.unwrap()
+}
+
+fn locked(who: u64) -> u64 {
+	pallet_balances::Locks::<Test>::get(who).iter().map(|lock| lock.amount).sum()
+}
+
+#[test]
+fn fees_and_tips_are_credited_to_reward_splits() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 250), (2, 750)]));
+		let issuance = Balances::total_issuance();
+
+		let (fee, tip) = (withdraw_fee(80), withdraw_fee(20));
+		DealWithFees::<Test>::on_unbalanceds([fee, tip].into_iter());
+		assert_eq!(PendingFees::<Test>::get(), 100);
+		assert_eq!((Balances::free_balance(1), Balances::free_balance(2)), (250, 750));
+
+		Coinbase::on_finalize(1);
+		assert_eq!(PendingFees::<Test>::get(), 0);
+
+		assert_eq!(Balances::total_issuance(), issuance);
+		assert_eq!(Balances::free_balance(PAYER), 1_000_000 - 100);
+		assert_eq!((Balances::free_balance(1), Balances::free_balance(2)), (275, 825));
+		assert_eq!((locked(1), locked(2)), (275, 825));
+		assert_eq!(Coinbase::reward_locks(1), Some(275));
+		assert_eq!(Coinbase::rewards(1).into_inner(), vec![(1, 275), (2, 825)]);
+	});
+}
+
+#[test]
+fn rounded_fees_are_fully_credited() {
+	new_test_ext().execute_with(|| {
+		let rewards = vec![(1, 333), (2, 333), (3, 334)];
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), rewards));
+		let issuance = Balances::total_issuance();
+
+		DealWithFees::<Test>::on_unbalanced(withdraw_fee(10));
+		Coinbase::on_finalize(1);
+
+		assert_eq!(Balances::total_issuance(), issuance);
+		let balances = [1, 2, 3].map(Balances::free_balance);
+		assert_eq!(balances, [336, 336, 338]);
+		assert_eq!(balances.iter().sum::<u64>(), EMISSION + 10);
+	});
+}
+
+#[test]
+fn fees_mature_with_rewards() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, EMISSION)]));
+		DealWithFees::<Test>::on_unbalanced(withdraw_fee(100));
+		Coinbase::on_finalize(1);
+		assert_eq!(locked(1), EMISSION + 100);
+
+		// The rewards of block 1 mature at block 3.
+		Coinbase::on_initialize(2);
+		assert_eq!(locked(1), EMISSION + 100);
+		Coinbase::on_initialize(3);
+		assert_eq!(locked(1), 0);
+		assert_eq!(Coinbase::reward_locks(1), None);
+		assert_eq!(Balances::free_balance(1), EMISSION + 100);
+	});
+}
+
+#[test]
+fn fees_are_burned_without_reward_splits() {
+	new_test_ext().execute_with(|| {
+		let issuance = Balances::total_issuance();
+
+		DealWithFees::<Test>::on_unbalanced(withdraw_fee(100));
+		Coinbase::on_finalize(1);
+
+		assert_eq!(Balances::total_issuance(), issuance - 100);
+		assert_eq!(PendingFees::<Test>::get(), 0);
+		assert!(Coinbase::rewards(1).is_empty());
+	});
+}
+
+#[test]
+fn fee_distribution_weight_is_charged_per_split() {
+	new_test_ext().execute_with(|| {
+		let call = Call::<Test>::coinbase { rewards: vec![(1, 400), (2, 600)] };
+		let fees_weight = <() as WeightInfo>::on_finalize(2);
+		assert_eq!(
+			call.get_dispatch_info().weight,
+			<() as WeightInfo>::coinbase(2).saturating_add(fees_weight)
+		);
+
+		// Without splits, the fees are only read and burned.
+		assert_eq!(
+			Coinbase::on_initialize(1),
+			<() as WeightInfo>::on_initialize(0).saturating_add(<() as WeightInfo>::on_finalize(0))
+		);
+	});
+}
+
+#[test]
+fn issuance_grows_by_block_rewards_only() {
+	new_test_ext().execute_with(|| {
+		let issuance = Balances::total_issuance();
+
+		for height in 1..=5 {
+			System::set_block_number(height);
+			Coinbase::on_initialize(height);
+			assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 400), (2, 600)]));
+			DealWithFees::<Test>::on_unbalanced(withdraw_fee(50));
+			Coinbase::on_finalize(height);
+		}
+
+		assert_eq!(Balances::total_issuance(), issuance + 5 * EMISSION);
+		assert_eq!(Balances::free_balance(1) + Balances::free_balance(2), 5 * (EMISSION + 50));
+		// Rewards and fees of blocks 4 and 5 are still locked.
+		assert_eq!(locked(1) + locked(2), 2 * (EMISSION + 50));
+	});
+}
+
+#[test]
+fn exponential_decay_emits_fraction_of_remaining_supply() {
+	assert_eq!(Decaying::reward(0), 0);
+	assert_eq!(Decaying::reward(1), 10_000_000);
+	assert_eq!(Decaying::reward(2), 9_900_000);
+	assert_eq!(Decaying::issued(2), 19_900_000);
+	assert_issued_is_sum::<Decaying>(500);
+	assert!(Decaying::issued(10_000) <= Supply::get());
+}
+
+#[test]
+fn halving_halves_reward_every_interval() {
+	assert_eq!(Halvings::reward(0), 0);
+	assert_eq!(Halvings::reward(10), 50);
+	assert_eq!(Halvings::reward(11), 25);
+	assert_eq!(Halvings::reward(31), 6);
+	assert_eq!(Halvings::issued(25), 500 + 250 + 5 * 12);
+	assert_eq!(Halvings::reward(10 * 128 + 1), 0);
+	assert_eq!(Halvings::issued(10_000), Halvings::issued(1_000));
+	assert_issued_is_sum::<Halvings>(100);
+}
+
+#[test]
+fn tail_emission_keeps_reward_at_floor() {
+	assert_eq!(Tail::reward(0), 0);
+	assert_eq!(Tail::reward(50), 3);
+	assert_eq!(Tail::reward(51), 3);
+	assert_eq!(Tail::reward(1_000), 3);
+	assert_eq!(Tail::issued(60), 500 + 250 + 120 + 60 + 30 + 10 * 3);
+	assert_issued_is_sum::<Tail>(100);
+}
+
+#[test]
+fn projected_issuance_follows_emission_curve() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, EMISSION)]));
+		let issuance = Balances::total_issuance();
+
+		assert_eq!(Coinbase::block_reward(5), EMISSION);
+		assert_eq!(Coinbase::projected_issuance(1), issuance);
+		assert_eq!(Coinbase::projected_issuance(5), issuance + 4 * EMISSION);
+		assert_eq!(Coinbase::projected_issuance(0), issuance - EMISSION);
+	});
+}
+
+#[test]
+fn reward_issuance_and_maturation_emit_events() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 250), (2, 750)]));
+		System::assert_last_event(
+			Event::RewardIssued { block: 1, splits: vec![(1, 250), (2, 750)] }.into(),
+		);
+		DealWithFees::<Test>::on_unbalanced(withdraw_fee(100));
+		Coinbase::on_finalize(1);
+
+		System::set_block_number(3);
+		Coinbase::on_initialize(3);
+		System::assert_has_event(Event::RewardMatured { account: 1, amount: 275 }.into());
+		System::assert_last_event(Event::RewardMatured { account: 2, amount: 825 }.into());
+	});
+}
+
+#[test]
+fn locked_rewards_list_unlock_heights() {
+	new_test_ext().execute_with(|| {
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 250), (2, 750)]));
+		System::set_block_number(2);
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), vec![(1, EMISSION)]));
+
+		let rewards = Coinbase::locked_rewards(&1);
+		assert_eq!(rewards.locked, 250 + EMISSION);
+		assert_eq!(rewards.maturing, vec![(3, 250), (4, EMISSION)]);
+
+		System::set_block_number(3);
+		Coinbase::on_initialize(3);
+		let rewards = Coinbase::locked_rewards(&1);
+		assert_eq!(rewards.locked, EMISSION);
+		assert_eq!(rewards.maturing, vec![(4, EMISSION)]);
+		assert_eq!(Coinbase::locked_rewards(&2), Default::default());
+	});
+}
+
+#[test]
+fn reward_splits_follow_share_weights() {
+	let splits =
+		|shares: &[(u64, u64)]| Coinbase::reward_splits(shares.iter().copied().collect(), 1000);
+
+	assert_eq!(splits(&[(1, 1), (2, 2)]), Ok(vec![(1, 333), (2, 667)]));
+	assert_eq!(splits(&[]), Err(InherentError::NoShares));
+	assert_eq!(splits(&[(1, 0), (2, 0)]), Err(InherentError::ZeroWeight));
+}
+
+#[test]
+fn check_inherent_validates_coinbase() {
+	new_test_ext().execute_with(|| {
+		let check = |rewards: Vec<(u64, u64)>, data: &InherentData| {
+			Coinbase::check_inherent(&Call::<Test>::coinbase { rewards }, data)
+		};
+		let no_shares = InherentData::new();
+
+		// The coinbase is checked for block 2 at the state of block 1.
+		assert_eq!(check(vec![(1, 400), (2, 600)], &no_shares), Ok(()));
+		assert_eq!(
+			check(vec![(1, 400), (2, 599)], &no_shares),
+			Err(InherentError::SumMismatch { expected: EMISSION as u128, actual: 999 })
+		);
+		assert_eq!(
+			check(vec![(1, 200); 5], &no_shares),
+			Err(InherentError::TooManySplits { splits: 5, max: 4 })
+		);
+		assert_eq!(check(vec![(1, 0), (2, 1000)], &no_shares), Err(InherentError::ZeroSplit));
+		assert_eq!(
+			check(vec![(1, 400), (2, 100), (1, 500)], &no_shares),
+			Err(InherentError::DuplicateSplit)
+		);
+
+		let shares = share_data(&[(1, 2), (2, 3)]);
+		assert_eq!(check(vec![(1, 400), (2, 600)], &shares), Ok(()));
+		assert_eq!(check(vec![(1, 500), (2, 500)], &shares), Err(InherentError::SplitMismatch));
+		assert_eq!(
+			check(vec![(1, 400), (2, 600)], &share_data(&[(1, 0), (2, 0)])),
+			Err(InherentError::ZeroWeight)
+		);
+
+		assert_eq!(
+			Coinbase::is_inherent_required(&no_shares),
+			Ok(Some(InherentError::MissingCoinbase))
+		);
+	});
+}
+
+fn create_inherent(data: &InherentData) -> Option<Vec<(u64, u64)>> {
+	Coinbase::create_inherent(data).map(|call| match call {
+		Call::coinbase { rewards } => rewards,
+		_ => unreachable!(),
+	})
+}
+
+#[test]
+fn missing_or_malformed_share_data_creates_no_coinbase() {
+	new_test_ext().execute_with(|| {
+		assert_eq!(create_inherent(&InherentData::new()), None);
+
+		let mut malformed = InherentData::new();
+		malformed.put_data(INHERENT_IDENTIFIER, &[7u8]).unwrap();
+		assert_eq!(create_inherent(&malformed), None);
+
+		assert_eq!(create_inherent(&share_data(&[])), None);
+		assert_eq!(create_inherent(&share_data(&[(1, 0), (2, 0)])), None);
+	});
+}
+
+#[test]
+fn coinbase_rejects_zero_and_duplicate_splits() {
+	new_test_ext().execute_with(|| {
+		assert_noop!(
+			Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 0), (2, 1000)]),
+			Error::<Test>::InvalidReward
+		);
+		assert_noop!(
+			Coinbase::coinbase(RawOrigin::None.into(), vec![(1, 400), (1, 600)]),
+			Error::<Test>::InvalidReward
+		);
+	});
+}
+
+#[test]
+fn zero_weight_shares_are_rejected() {
+	new_test_ext().execute_with(|| {
+		let shares = share_data(&[(1, 0), (2, 3), (3, 1)]);
+		assert_eq!(create_inherent(&shares), None);
+		assert_eq!(
+			Coinbase::check_inherent(
+				&Call::coinbase { rewards: vec![(2, 750), (3, 250)] },
+				&shares
+			),
+			Err(InherentError::ZeroWeight)
+		);
+	});
+}
+
+#[test]
+fn created_coinbase_passes_checks() {
+	let cases: [&[(u64, u64)]; 6] = [
+		&[(1, 1)],
+		&[(1, 2), (2, 3)],
+		&[(1, 10), (2, 9), (3, 8), (4, 1), (5, 2), (6, 3)],
+		&[(1, u64::MAX), (2, u64::MAX), (3, 1)],
+		&[(1, u64::MAX), (2, 1)],
+		&[(1, 1), (2, 1), (3, 1), (4, 1), (5, 1), (6, 1), (7, 1)],
+	];
+
+	for shares in cases {
+		new_test_ext().execute_with(|| {
+			let data = share_data(shares);
+			let shares = shares.iter().copied().collect::<InherentTypeOf<Test>>();
+			assert_eq!(validate_shares(&shares), Ok(()));
+
+			// Share data accepted by the node always creates a coinbase.
+			let call = Coinbase::create_inherent(&data).expect("coinbase is created");
+			assert_ok!(call.clone().dispatch_bypass_filter(RawOrigin::None.into()));
+
+			System::set_block_number(0);
+			assert_eq!(Coinbase::check_inherent(&call, &data), Ok(()), "{:?}", shares);
+			assert_eq!(Coinbase::check_inherent(&call, &InherentData::new()), Ok(()));
+		});
+	}
+}
+
+#[test]
+fn smallest_shares_are_spread_over_kept_splits() {
+	new_test_ext().execute_with(|| {
+		let shares = share_data(&[(1, 10), (2, 9), (3, 8), (4, 1), (5, 2), (6, 3)]);
+		let rewards = create_inherent(&shares).unwrap();
+
+		// 4 and 5 get no split, and their part of the reward goes to all kept splits by weight.
+		assert_eq!(rewards, vec![(1, 333), (2, 300), (3, 267), (6, 100)]);
+		assert!(rewards.iter().all(|(dest, _)| ![4, 5].contains(dest)));
+		let total = rewards.iter().map(|(_, value)| value).sum::<u64>();
+		assert_eq!(total, EMISSION);
+		assert_ok!(Coinbase::coinbase(RawOrigin::None.into(), rewards.clone()));
+
+		System::set_block_number(0);
+		assert_eq!(Coinbase::check_inherent(&Call::coinbase { rewards }, &shares), Ok(()));
+	});
+}
+
+#[test]
+fn shares_earning_nothing_get_no_split() {
+	new_test_ext().execute_with(|| {
+		let shares = share_data(&[(1, u64::MAX), (2, 1)]);
+		assert_eq!(create_inherent(&shares), Some(vec![(1, EMISSION)]));
+	});
+}
+
+#[test]
+fn large_weights_do_not_overflow() {
+	new_test_ext().execute_with(|| {
+		let shares = share_data(&[(1, u64::MAX), (2, u64::MAX), (3, 1)]);
+		assert_eq!(create_inherent(&shares), Some(vec![(1, 499), (2, 500), (3, 1)]));
+	});
+}
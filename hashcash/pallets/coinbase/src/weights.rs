@@ -0,0 +1,93 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+//! Placeholder weights for `pallet_coinbase`.
+//!
+//! These are not benchmark results. They count the storage accesses of each path, and charge a
+//! generous execution time per reward split. Replace this file with the output of the benchmarks
+//! on the reference hardware:
+//!
+//! ```sh
+//! ./target/release/hashcash benchmark pallet --chain=dev --pallet=pallet_coinbase \
+//!   --extrinsic='*' --steps=50 --repeat=20 --wasm-execution=compiled \
+//!   --output=hashcash/pallets/coinbase/src/weights.rs
+//! ```
+
+use core::marker::PhantomData;
+use frame_support::{
+	traits::Get,
+	weights::{constants::RocksDbWeight, RuntimeDbWeight, Weight},
+};
+
+/// Weight functions needed for `pallet_coinbase`.
+pub trait WeightInfo {
+	fn coinbase(n: u32) -> Weight;
+	fn on_initialize(n: u32) -> Weight;
+	fn on_finalize(n: u32) -> Weight;
+}
+
+/// Execution time of a reward split, covering a balance deposit and a lock update.
+const SPLIT_TIME: u64 = 50_000_000;
+/// Proof size of a reward split, covering an account, its reward lock and balance locks.
+const SPLIT_PROOF_SIZE: u64 = 9_000;
+/// Proof size of the rewards of a block, bounded by the maximum number of splits.
+const REWARDS_PROOF_SIZE: u64 = 52_000;
+
+/// Storage accesses of `coinbase` with `n` splits, and of maturing `n` rewards.
+///
+/// Every split reads and writes an account, its reward lock and its balance locks, and reads its
+/// balance freezes. `coinbase` also reads the block number and the total issuance, and writes the
+/// total issuance and the rewards of the block. Maturing rewards reads and removes them.
+fn coinbase_weight(db: RuntimeDbWeight, n: u32) -> Weight {
+	let n = n as u64;
+	Weight::from_parts(10_000_000 + SPLIT_TIME * n, 1_000 + SPLIT_PROOF_SIZE * n)
+		.saturating_add(db.reads_writes(2 + 4 * n, 2 + 3 * n))
+}
+
+fn on_initialize_weight(db: RuntimeDbWeight, n: u32) -> Weight {
+	let n = n as u64;
+	Weight::from_parts(5_000_000 + SPLIT_TIME * n, REWARDS_PROOF_SIZE + SPLIT_PROOF_SIZE * n)
+		.saturating_add(db.reads_writes(1 + 3 * n, 1 + 3 * n))
+}
+
+/// Storage accesses of crediting the fees of a block to `n` splits.
+///
+/// Every split is updated as in `coinbase`. The pending fees, the rewards of the block and the
+/// total issuance are read and written.
+fn on_finalize_weight(db: RuntimeDbWeight, n: u32) -> Weight {
+	let n = n as u64;
+	Weight::from_parts(5_000_000 + SPLIT_TIME * n, REWARDS_PROOF_SIZE + SPLIT_PROOF_SIZE * n)
+		.saturating_add(db.reads_writes(3 + 4 * n, 3 + 3 * n))
+}
+
+/// Weights for `pallet_coinbase` using the database weights of the runtime.
+pub struct SubstrateWeight<T>(PhantomData<T>);
+
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+	fn coinbase(n: u32) -> Weight {
+		coinbase_weight(T::DbWeight::get(), n)
+	}
+
+	fn on_initialize(n: u32) -> Weight {
+		on_initialize_weight(T::DbWeight::get(), n)
+	}
+
+	fn on_finalize(n: u32) -> Weight {
+		on_finalize_weight(T::DbWeight::get(), n)
+	}
+}
+
+// For backwards compatibility and tests.
+impl WeightInfo for () {
+	fn coinbase(n: u32) -> Weight {
+		coinbase_weight(RocksDbWeight::get(), n)
+	}
+
+	fn on_initialize(n: u32) -> Weight {
+		on_initialize_weight(RocksDbWeight::get(), n)
+	}
+
+	fn on_finalize(n: u32) -> Weight {
+		on_finalize_weight(RocksDbWeight::get(), n)
+	}
+}
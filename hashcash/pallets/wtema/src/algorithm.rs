@@ -0,0 +1,419 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+//! Difficulty adjustment algorithms.
+//!
+//! Every algorithm calculates the difficulty of the next block from an anchor block, which is the
+//! first block after genesis, and a window of the most recent blocks. Timestamps are in
+//! milliseconds, and difficulties are converted to targets as `U256::MAX / difficulty`.
+//!
+//! Solvetimes are clamped to [`Params::solvetime_bounds`], so that a single long solvetime cannot
+//! move the difficulty of WTEMA and LWMA by more than a bounded step. ASERT schedules blocks from
+//! the anchor instead. Forged timestamps are bounded by the future time limit of the node, and
+//! `pallet_timestamp` requires them to increase by at least its minimum period, so solvetimes are
+//! never negative and the lower bound has no effect on a chain.
+
+use frame_support::pallet_prelude::*;
+use sp_core::U256;
+
+/// Number, timestamp and difficulty of a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Encode, Decode, MaxEncodedLen, TypeInfo)]
+pub struct BlockInfo<BlockNumber, Moment, Difficulty> {
+	pub number: BlockNumber,
+	pub timestamp: Moment,
+	pub difficulty: Difficulty,
+}
+
+/// Block info as seen by the algorithms.
+pub type Sample = BlockInfo<u64, u64, U256>;
+
+/// Parameters shared by the algorithms.
+#[derive(Clone, Copy, Debug)]
+pub struct Params {
+	/// Desired block time in milliseconds.
+	pub target_block_time: u64,
+	/// Smoothing factor, in blocks.
+	pub filter: u32,
+	/// Lower and upper bounds of a solvetime, in target block times.
+	pub solvetime_bounds: (i32, i32),
+}
+
+/// Difficulty adjustment algorithm.
+pub trait Algorithm {
+	/// Number of the most recent blocks needed to calculate the next difficulty.
+	fn window(params: &Params) -> u32;
+
+	/// Calculates the difficulty of the block following the last of `recent`.
+	///
+	/// `recent` contains up to [`Self::window`] blocks, oldest first, and is never empty.
+	fn next_difficulty(params: &Params, anchor: &Sample, recent: &[Sample]) -> U256;
+}
+
+fn elapsed(prev: &Sample, block: &Sample) -> i128 {
+	block.timestamp as i128 - prev.timestamp as i128
+}
+
+fn solvetime(params: &Params, prev: &Sample, block: &Sample) -> i128 {
+	let target_block_time = params.target_block_time as i128;
+	let (min, max) = params.solvetime_bounds;
+	elapsed(prev, block)
+		.min(max as i128 * target_block_time)
+		.max(min as i128 * target_block_time)
+}
+
+fn to_target(difficulty: U256) -> U256 {
+	U256::MAX / difficulty.max(U256::one())
+}
+
+fn to_difficulty(target: U256) -> U256 {
+	U256::MAX / target.max(U256::one())
+}
+
+/// Weighted-target exponential moving average.
+///
+/// `target = prior_target * (1 + t/T/N - 1/N)`
+/// where
+///   N = smoothing constant aka filter
+///   t = prior block solvetime
+///   T = desired average block time
+pub struct Wtema;
+
+impl Algorithm for Wtema {
+	fn window(_params: &Params) -> u32 {
+		2
+	}
+
+	fn next_difficulty(params: &Params, anchor: &Sample, recent: &[Sample]) -> U256 {
+		let (prev, last) = match recent {
+			[.., prev, last] => (prev, last),
+			[last] => return last.difficulty,
+			[] => return anchor.difficulty,
+		};
+		let target_block_time = params.target_block_time as i128;
+		let filter = params.filter.max(1) as i128;
+
+		let prior_target = to_target(last.difficulty);
+		let factor =
+			(target_block_time * filter + solvetime(params, prev, last) - target_block_time).max(1);
+		let target = (prior_target / U256::from((target_block_time * filter) as u128))
+			.saturating_mul(U256::from(factor as u128));
+
+		to_difficulty(target)
+	}
+}
+
+/// Linearly weighted moving average.
+///
+/// `D = avg(D) * T * (N + 1) / 2 / sum(i * t_i)`
+/// where the N most recent solvetimes `t_i` are weighted by `i`, the newest by N.
+pub struct Lwma;
+
+impl Algorithm for Lwma {
+	fn window(params: &Params) -> u32 {
+		params.filter.max(1) + 1
+	}
+
+	fn next_difficulty(params: &Params, anchor: &Sample, recent: &[Sample]) -> U256 {
+		let n = recent.len() as i128 - 1;
+		if n < 1 {
+			return recent.last().unwrap_or(anchor).difficulty;
+		}
+		let target_block_time = params.target_block_time as i128;
+
+		let mut weighted_solvetimes = 0;
+		let mut sum_difficulty = U256::zero();
+		for (i, pair) in recent.windows(2).enumerate() {
+			weighted_solvetimes += (i as i128 + 1) * solvetime(params, &pair[0], &pair[1]);
+			sum_difficulty = sum_difficulty.saturating_add(pair[1].difficulty);
+		}
+		// Limits the difficulty to 10 times the average in case of negative solvetimes.
+		let weighted_solvetimes = weighted_solvetimes.max(n * (n + 1) * target_block_time / 20);
+
+		sum_difficulty.saturating_mul(U256::from((target_block_time * (n + 1)) as u128)) /
+			U256::from((2 * weighted_solvetimes) as u128)
+	}
+}
+
+/// Absolutely scheduled exponentially rising targets, as aserti3-2d.
+///
+/// `target = anchor_target * 2^((t - t_anchor - T * (h - h_anchor)) / (T * N))`
+/// where the half-life of a schedule deviation is N blocks.
+pub struct Asert;
+
+/// Fractional bits of the ASERT exponent.
+const RADIX: u32 = 16;
+
+impl Algorithm for Asert {
+	fn window(_params: &Params) -> u32 {
+		1
+	}
+
+	fn next_difficulty(params: &Params, anchor: &Sample, recent: &[Sample]) -> U256 {
+		let last = recent.last().unwrap_or(anchor);
+		let target_block_time = params.target_block_time as i128;
+		let half_life = target_block_time * params.filter.max(1) as i128;
+
+		let schedule = elapsed(anchor, last) -
+			target_block_time * (last.number as i128 - anchor.number as i128);
+		let exponent = (schedule << RADIX) / half_life;
+		let shifts = exponent >> RADIX;
+		let frac = (exponent - (shifts << RADIX)) as u128;
+
+		// 2^frac approximated by a cubic polynomial with the error below 0.013%.
+		let factor = (1u128 << RADIX) +
+			((195_766_423_245_049 * frac +
+				971_821_376 * frac.pow(2) +
+				5_127 * frac.pow(3) +
+				(1 << 47)) >>
+				48);
+
+		let target = to_target(anchor.difficulty).saturating_mul(U256::from(factor));
+		let shifts = shifts - RADIX as i128;
+		let target = match shifts {
+			s if s >= 256 => U256::MAX,
+			s if s >= 0 => {
+				let shifted = target << s as usize;
+				match shifted >> s as usize == target {
+					true => shifted,
+					false => U256::MAX,
+				}
+			},
+			s if s > -256 => target >> (-s) as usize,
+			_ => U256::zero(),
+		};
+
+		to_difficulty(target)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// 2 minutes in milliseconds.
+	const T: u64 = 120_000;
+	const PARAMS: Params =
+		Params { target_block_time: T, filter: 72, solvetime_bounds: (i32::MIN, i32::MAX) };
+	const BOUNDED: Params = Params { solvetime_bounds: (-6, 6), ..PARAMS };
+	/// Future time limit of the node, 1 minute.
+	const FTL: u64 = T / 2;
+	/// Minimum period between timestamps enforced by `pallet_timestamp`, 3 seconds.
+	const MIN_PERIOD: u64 = T / 40;
+
+	/// Deterministic pseudo-random factors with the exponential distribution of solvetimes.
+	struct Exponential(u64);
+
+	impl Exponential {
+		fn next(&mut self) -> f64 {
+			self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+			let uniform = ((self.0 >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+			-uniform.ln()
+		}
+	}
+
+	/// Timestamp reported for a block, given its true timestamp and the previous timestamp.
+	type Reporter<'a> = &'a dyn Fn(u64, u64, u64) -> u64;
+
+	/// Simulates mining with the hashrate, in difficulty per millisecond, of each block.
+	fn simulate<A: Algorithm>(
+		params: &Params,
+		hashrate: impl Fn(u64) -> f64,
+		report: Reporter,
+	) -> Vec<Sample> {
+		let mut random = Exponential(42);
+		let initial = U256::from((hashrate(1) * T as f64) as u128);
+		let anchor = Sample { number: 1, timestamp: T, difficulty: initial };
+
+		let mut blocks = vec![anchor];
+		let mut time = T;
+		let mut difficulty = initial;
+		for number in 2..3000 {
+			let prev = blocks.last().unwrap().timestamp;
+			time += (difficulty.low_u128() as f64 / hashrate(number) * random.next()) as u64;
+			let timestamp = report(number, time, prev);
+			assert!(timestamp >= prev + MIN_PERIOD, "timestamps increase by the minimum period");
+			// A block too far in the future is only accepted once the future time limit allows it.
+			time = time.max(timestamp.saturating_sub(FTL));
+
+			blocks.push(Sample { number, timestamp, difficulty });
+			let window = A::window(params) as usize;
+			let recent = &blocks[blocks.len().saturating_sub(window)..];
+			difficulty = A::next_difficulty(params, &anchor, recent).max(U256::one());
+		}
+		blocks
+	}
+
+	fn honest(_number: u64, time: u64, prev: u64) -> u64 {
+		time.max(prev + MIN_PERIOD)
+	}
+
+	fn average_solvetime(blocks: &[Sample]) -> f64 {
+		let first = blocks.first().unwrap();
+		let last = blocks.last().unwrap();
+		(last.timestamp - first.timestamp) as f64 / (last.number - first.number) as f64
+	}
+
+	fn average_difficulty(blocks: &[Sample]) -> f64 {
+		blocks.iter().map(|block| block.difficulty.low_u128() as f64).sum::<f64>() /
+			blocks.len() as f64
+	}
+
+	fn check_steady_hashrate<A: Algorithm>() {
+		let blocks = simulate::<A>(&PARAMS, |_| 1000.0, &honest);
+
+		let solvetime = average_solvetime(&blocks[500..]);
+		assert!((solvetime / T as f64 - 1.0).abs() < 0.05, "average solvetime: {}", solvetime);
+	}
+
+	fn check_hashrate_shock<A: Algorithm>() {
+		let hashrate = |number| if (1000..2000).contains(&number) { 10_000.0 } else { 1000.0 };
+		let blocks = simulate::<A>(&PARAMS, hashrate, &honest);
+
+		// The difficulty follows the hashrate after a few filter lengths.
+		let difficulty = average_difficulty(&blocks[1500..2000]);
+		assert!(
+			(difficulty / (10_000.0 * T as f64) - 1.0).abs() < 0.1,
+			"difficulty: {}",
+			difficulty
+		);
+		let difficulty = average_difficulty(&blocks[2500..]);
+		assert!((difficulty / (1000.0 * T as f64) - 1.0).abs() < 0.1, "difficulty: {}", difficulty);
+	}
+
+	fn check_timestamp_manipulation<A: Algorithm>() {
+		// A fifth of the blocks are mined by an attacker reporting timestamps at the future time
+		// limit, which honest miners have to follow as timestamps cannot decrease.
+		let manipulated = |number: u64, time: u64, prev: u64| match number % 5 {
+			0 => (time + FTL).max(prev + MIN_PERIOD),
+			_ => honest(number, time, prev),
+		};
+		let blocks = simulate::<A>(&BOUNDED, |_| 1000.0, &manipulated);
+
+		let difficulty = average_difficulty(&blocks[500..]);
+		assert!((difficulty / (1000.0 * T as f64) - 1.0).abs() < 0.1, "difficulty: {}", difficulty);
+	}
+
+	fn check_alternating_timestamps<A: Algorithm>() {
+		// An attacker mining every block alternates timestamps at the future time limit with the
+		// earliest timestamps allowed, so that solvetimes alternate between long and minimal.
+		let alternating = |number: u64, time: u64, prev: u64| match number % 2 {
+			1 => (time + FTL).max(prev + MIN_PERIOD),
+			_ => prev + MIN_PERIOD,
+		};
+		let blocks = simulate::<A>(&BOUNDED, |_| 1000.0, &alternating);
+
+		// The forged time is bounded by the future time limit, so the difficulty stays put.
+		let difficulty = average_difficulty(&blocks[500..]);
+		assert!((difficulty / (1000.0 * T as f64) - 1.0).abs() < 0.1, "difficulty: {}", difficulty);
+	}
+
+	#[test]
+	fn wtema_steady_hashrate() {
+		check_steady_hashrate::<Wtema>();
+	}
+
+	#[test]
+	fn lwma_steady_hashrate() {
+		check_steady_hashrate::<Lwma>();
+	}
+
+	#[test]
+	fn asert_steady_hashrate() {
+		check_steady_hashrate::<Asert>();
+	}
+
+	#[test]
+	fn wtema_hashrate_shock() {
+		check_hashrate_shock::<Wtema>();
+	}
+
+	#[test]
+	fn lwma_hashrate_shock() {
+		check_hashrate_shock::<Lwma>();
+	}
+
+	#[test]
+	fn asert_hashrate_shock() {
+		check_hashrate_shock::<Asert>();
+	}
+
+	#[test]
+	fn wtema_timestamp_manipulation() {
+		check_timestamp_manipulation::<Wtema>();
+	}
+
+	#[test]
+	fn lwma_timestamp_manipulation() {
+		check_timestamp_manipulation::<Lwma>();
+	}
+
+	#[test]
+	fn asert_timestamp_manipulation() {
+		check_timestamp_manipulation::<Asert>();
+	}
+
+	#[test]
+	fn wtema_alternating_timestamps() {
+		check_alternating_timestamps::<Wtema>();
+	}
+
+	#[test]
+	fn lwma_alternating_timestamps() {
+		check_alternating_timestamps::<Lwma>();
+	}
+
+	#[test]
+	fn asert_alternating_timestamps() {
+		check_alternating_timestamps::<Asert>();
+	}
+
+	#[test]
+	fn wtema_forward_timestamp_is_clamped() {
+		let prev = Sample { number: 1, timestamp: 0, difficulty: U256::from(1000 * T) };
+		let forged = Sample { number: 2, timestamp: 720 * T, ..prev };
+		let ratio = |params| {
+			Wtema::next_difficulty(params, &prev, &[prev, forged]).low_u64() as f64 /
+				(1000 * T) as f64
+		};
+
+		// A block timestamped a day ahead cuts the difficulty by 11 times.
+		assert!(ratio(&PARAMS) < 0.1);
+		// Within the bounds, the difficulty drops by (N + 5) / N at most.
+		assert!((ratio(&BOUNDED) - 72.0 / 77.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn lwma_forward_timestamp_is_clamped() {
+		let mut recent = (0..73)
+			.map(|number| Sample {
+				number,
+				timestamp: number * T,
+				difficulty: U256::from(1000 * T),
+			})
+			.collect::<Vec<_>>();
+		recent[72].timestamp = 791 * T;
+		let ratio = |params| {
+			Lwma::next_difficulty(params, &recent[0], &recent).low_u64() as f64 / (1000 * T) as f64
+		};
+
+		// The newest solvetime has the largest weight, so a day ahead cuts the difficulty by 20
+		// times.
+		assert!(ratio(&PARAMS) < 0.05);
+		// Within the bounds, the difficulty drops by 2628 / 2988 at most.
+		assert!((ratio(&BOUNDED) - 2628.0 / 2988.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn asert_follows_schedule() {
+		let anchor = Sample { number: 1, timestamp: 0, difficulty: U256::from(1_000_000) };
+		let at = |number, timestamp| {
+			Asert::next_difficulty(&PARAMS, &anchor, &[Sample { number, timestamp, ..anchor }])
+		};
+
+		assert_eq!(at(101, 100 * T), anchor.difficulty);
+		// One half-life behind or ahead of the schedule halves or doubles the difficulty.
+		let behind = at(101, 172 * T).low_u64() as f64 / 500_000.0;
+		let ahead = at(173, 100 * T).low_u64() as f64 / 2_000_000.0;
+		assert!((behind - 1.0).abs() < 0.001 && (ahead - 1.0).abs() < 0.001);
+	}
+}
@@ -1,26 +1,35 @@
 // Copyright (c) Ryuichi Sakamoto
 // SPDX-License-Identifier: Apache-2.0
 
-//! WTEMA difficulty adjustment algorithm.
-
-// target = prior_target * (1 + t/T/N - 1/N);
-// where
-//   N = smoothing constant aka filter
-//   t = prior block solvetime
-//   T = desired average block time
+//! Difficulty adjustment with WTEMA, LWMA or ASERT, selected by [`Config::Algorithm`].
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod algorithm;
+pub mod migrations;
+
+pub use algorithm::{Algorithm, Asert, BlockInfo, Lwma, Params, Sample, Wtema};
 pub use pallet::*;
 
-use frame_support::traits::{Get, OnTimestampSet};
+use frame_support::{
+	traits::{Get, OnTimestampSet},
+	BoundedVec,
+};
+use frame_system::pallet_prelude::BlockNumberFor;
 use parity_scale_codec::FullCodec;
 use sp_core::U256;
 use sp_runtime::{
-	sp_std::fmt::Debug,
-	traits::{One, SaturatedConversion, UniqueSaturatedFrom},
+	sp_std::{fmt::Debug, marker::PhantomData, vec::Vec},
+	traits::{CheckedSub, SaturatedConversion, UniqueSaturatedFrom},
 };
 
+/// Block info stored by the pallet.
+pub type BlockInfoOf<T> = BlockInfo<
+	BlockNumberFor<T>,
+	<T as pallet_timestamp::Config>::Moment,
+	<T as Config>::Difficulty,
+>;
+
 #[frame_support::pallet]
 pub mod pallet {
 	use super::*;
@@ -42,14 +51,28 @@ pub mod pallet {
 			+ Into<U256>
 			+ PartialOrd;
 
+		/// Difficulty adjustment algorithm.
+		type Algorithm: Algorithm;
+
 		#[pallet::constant]
 		type TargetBlockTime: Get<Self::Moment>;
 
+		/// Smoothing factor of the algorithm, in blocks.
 		#[pallet::constant]
 		type Filter: Get<u32>;
 
+		/// Lower and upper bounds of a solvetime, in target block times.
+		///
+		/// The lower bound is inert, as `pallet_timestamp` keeps solvetimes positive.
+		#[pallet::constant]
+		type SolvetimeBounds: Get<(i32, i32)>;
+
 		#[pallet::constant]
 		type MinDifficulty: Get<Self::Difficulty>;
+
+		/// Number of recent blocks kept in the difficulty history.
+		#[pallet::constant]
+		type HistorySize: Get<u32>;
 	}
 
 	#[pallet::storage]
@@ -60,6 +83,19 @@ pub mod pallet {
 	#[pallet::getter(fn timestamps)]
 	pub type MostRecentTimestamp<T: Config> = StorageValue<_, T::Moment, ValueQuery>;
 
+	/// First block adjusting the difficulty, which ASERT schedules blocks from.
+	#[pallet::storage]
+	pub type Anchor<T: Config> = StorageValue<_, BlockInfoOf<T>, OptionQuery>;
+
+	/// Most recent blocks needed by the algorithm, oldest first.
+	#[pallet::storage]
+	pub type RecentBlocks<T: Config> =
+		StorageValue<_, BoundedVec<BlockInfoOf<T>, RecentBlocksLimit<T>>, ValueQuery>;
+
+	/// Ring buffer of recent blocks, indexed by block number modulo [`Config::HistorySize`].
+	#[pallet::storage]
+	pub type History<T: Config> = StorageMap<_, Twox64Concat, u32, BlockInfoOf<T>, OptionQuery>;
+
 	#[pallet::genesis_config]
 	pub struct GenesisConfig<T: Config> {
 		pub difficulty: T::Difficulty,
@@ -80,22 +116,121 @@ pub mod pallet {
 	}
 }
 
-impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T>
-where
-	T::Moment: Into<U256>,
-{
+/// Number of recent blocks kept for the algorithm.
+pub struct RecentBlocksLimit<T>(PhantomData<T>);
+
+impl<T: Config> Get<u32> for RecentBlocksLimit<T> {
+	fn get() -> u32 {
+		T::Algorithm::window(&Pallet::<T>::params())
+	}
+}
+
+impl<T: Config> Pallet<T> {
+	/// Returns the parameters of the algorithm.
+	pub fn params() -> Params {
+		Params {
+			target_block_time: T::TargetBlockTime::get().saturated_into(),
+			filter: T::Filter::get(),
+			solvetime_bounds: T::SolvetimeBounds::get(),
+		}
+	}
+
+	/// Returns the info of a block, if it is in the history.
+	pub fn history(number: BlockNumberFor<T>) -> Option<BlockInfoOf<T>> {
+		History::<T>::get(Self::history_slot(number)?).filter(|block| block.number == number)
+	}
+
+	/// Returns the difficulty of a block, if it is in the history.
+	pub fn difficulty_at(number: BlockNumberFor<T>) -> Option<T::Difficulty> {
+		Self::history(number).map(|block| block.difficulty)
+	}
+
+	/// Returns the average solvetime of the last `window` blocks.
+	pub fn average_solvetime(window: u32) -> Option<T::Moment> {
+		let (window, first, last) = Self::history_window(window)?;
+		let elapsed = Self::elapsed(&first, &last)?;
+
+		Some(T::Moment::saturated_from(elapsed / window as u64))
+	}
+
+	/// Returns the network hashrate in hashes per second, estimated from the last `window` blocks.
+	pub fn network_hashrate_estimate(window: u32) -> Option<T::Difficulty> {
+		let (window, first, last) = Self::history_window(window)?;
+		let elapsed = Self::elapsed(&first, &last)?;
+		if elapsed == 0 {
+			return None
+		}
+
+		// Work done after the first block, as the difficulty is the expected number of hashes.
+		let work = (1..=window).try_fold(U256::zero(), |work, offset| {
+			let difficulty = Self::difficulty_at(first.number + offset.into())?;
+			Some(work.saturating_add(difficulty.into()))
+		})?;
+
+		Some(T::Difficulty::saturated_from(
+			work.saturating_mul(U256::from(1000)) / U256::from(elapsed),
+		))
+	}
+
+	fn history_slot(number: BlockNumberFor<T>) -> Option<u32> {
+		match T::HistorySize::get() {
+			0 => None,
+			size => Some((number % size.into()).saturated_into()),
+		}
+	}
+
+	/// Returns the window clamped to the history, with its first and last blocks.
+	fn history_window(window: u32) -> Option<(u32, BlockInfoOf<T>, BlockInfoOf<T>)> {
+		let window = window.min(T::HistorySize::get().saturating_sub(1));
+		if window == 0 {
+			return None
+		}
+		let last = Self::history(frame_system::Pallet::<T>::block_number())?;
+		let first = Self::history(last.number.checked_sub(&window.into())?)?;
+
+		Some((window, first, last))
+	}
+
+	/// Returns the milliseconds between two blocks.
+	fn elapsed(first: &BlockInfoOf<T>, last: &BlockInfoOf<T>) -> Option<u64> {
+		last.timestamp
+			.saturated_into::<u64>()
+			.checked_sub(first.timestamp.saturated_into())
+	}
+
+	fn sample(block: &BlockInfoOf<T>) -> Sample {
+		Sample {
+			number: block.number.saturated_into(),
+			timestamp: block.timestamp.saturated_into(),
+			difficulty: block.difficulty.into(),
+		}
+	}
+}
+
+impl<T: Config> OnTimestampSet<T::Moment> for Pallet<T> {
 	fn on_timestamp_set(now: T::Moment) {
-		let block_time = match frame_system::Pallet::<T>::block_number() {
-			n if n <= One::one() => T::TargetBlockTime::get(),
-			_ => now - MostRecentTimestamp::<T>::get(),
+		let block = BlockInfo {
+			number: frame_system::Pallet::<T>::block_number(),
+			timestamp: now,
+			difficulty: Difficulty::<T>::get(),
 		};
-		let desired_block_time = T::TargetBlockTime::get().into();
-		let prior_target = U256::max_value() / Difficulty::<T>::get().into();
-		let filter = T::Filter::get();
+		if let Some(slot) = Self::history_slot(block.number) {
+			History::<T>::insert(slot, block);
+		}
 
-		let target = (prior_target / (desired_block_time * filter))
-			.saturating_mul(desired_block_time * filter + block_time - desired_block_time);
-		let mut difficulty = T::Difficulty::saturated_from(U256::max_value() / target);
+		let anchor = Anchor::<T>::get().unwrap_or_else(|| {
+			Anchor::<T>::put(block);
+			block
+		});
+
+		let mut recent = RecentBlocks::<T>::get().into_inner();
+		recent.push(block);
+		let excess = recent.len().saturating_sub(RecentBlocksLimit::<T>::get() as usize);
+		recent.drain(..excess);
+
+		let samples = recent.iter().map(Self::sample).collect::<Vec<_>>();
+		let next = T::Algorithm::next_difficulty(&Self::params(), &Self::sample(&anchor), &samples);
+		let mut difficulty = T::Difficulty::saturated_from(next);
 
 		if difficulty < T::MinDifficulty::get() {
 			difficulty = T::MinDifficulty::get();
@@ -103,5 +238,129 @@ where
 
 		Difficulty::<T>::put(difficulty);
 		MostRecentTimestamp::<T>::put(now);
+		RecentBlocks::<T>::put(BoundedVec::truncate_from(recent));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::{self as pallet_wtema, *};
+
+	use frame_support::{
+		derive_impl, parameter_types,
+		traits::{ConstU128, ConstU32, ConstU64},
+	};
+	use sp_runtime::BuildStorage;
+
+	type Block = frame_system::mocking::MockBlock<Test>;
+
+	frame_support::construct_runtime!(
+		pub enum Test {
+			System: frame_system,
+			Wtema: pallet_wtema,
+		}
+	);
+
+	#[derive_impl(frame_system::config_preludes::TestDefaultConfig as frame_system::DefaultConfig)]
+	impl frame_system::Config for Test {
+		type Block = Block;
+	}
+
+	impl pallet_timestamp::Config for Test {
+		type Moment = u64;
+		type OnTimestampSet = Wtema;
+		type MinimumPeriod = ConstU64<1>;
+		type WeightInfo = ();
+	}
+
+	parameter_types! {
+		pub const SolvetimeBounds: (i32, i32) = (-6, 6);
+	}
+
+	impl pallet_wtema::Config for Test {
+		type Difficulty = u128;
+		type Algorithm = pallet_wtema::Wtema;
+		type TargetBlockTime = ConstU64<1000>;
+		type Filter = ConstU32<4>;
+		type SolvetimeBounds = SolvetimeBounds;
+		type MinDifficulty = ConstU128<1>;
+		type HistorySize = ConstU32<4>;
+	}
+
+	fn new_test_ext() -> sp_io::TestExternalities {
+		let mut storage = frame_system::GenesisConfig::<Test>::default().build_storage().unwrap();
+		pallet_wtema::GenesisConfig::<Test> { difficulty: 1_000_000 }
+			.assimilate_storage(&mut storage)
+			.unwrap();
+		storage.into()
+	}
+
+	/// Produces blocks from #1 with the given timestamps.
+	fn produce_blocks(timestamps: &[u64]) {
+		for (index, timestamp) in timestamps.iter().enumerate() {
+			System::set_block_number(index as u64 + 1);
+			<Wtema as OnTimestampSet<u64>>::on_timestamp_set(*timestamp);
+		}
+	}
+
+	#[test]
+	fn history_wraps_around() {
+		new_test_ext().execute_with(|| {
+			produce_blocks(&[1000, 2000, 3000, 4000, 5000, 6000]);
+
+			// Blocks #1 and #2 have been overwritten by blocks #5 and #6.
+			assert_eq!(Wtema::history(1), None);
+			assert_eq!(Wtema::history(2), None);
+			assert_eq!(Wtema::difficulty_at(2), None);
+			for number in 3..=6 {
+				let block = Wtema::history(number).unwrap();
+				assert_eq!((block.number, block.timestamp), (number, number * 1000));
+			}
+			assert_eq!(Wtema::history(7), None);
+		});
+	}
+
+	#[test]
+	fn window_is_clamped_to_history() {
+		new_test_ext().execute_with(|| {
+			produce_blocks(&[1000, 2000, 3000, 4500, 6000, 7500]);
+
+			// Only the 3 solvetimes between blocks #3 and #6 are kept.
+			assert_eq!(Wtema::average_solvetime(100), Some(1500));
+			assert_eq!(Wtema::average_solvetime(100), Wtema::average_solvetime(3));
+			assert_eq!(Wtema::average_solvetime(1), Some(1500));
+			assert!(Wtema::network_hashrate_estimate(100).is_some());
+			assert_eq!(Wtema::network_hashrate_estimate(100), Wtema::network_hashrate_estimate(3));
+		});
+	}
+
+	#[test]
+	fn zero_window_has_no_estimate() {
+		new_test_ext().execute_with(|| {
+			produce_blocks(&[1000, 2000, 3000]);
+
+			assert_eq!(Wtema::average_solvetime(0), None);
+			assert_eq!(Wtema::network_hashrate_estimate(0), None);
+		});
+	}
+
+	#[test]
+	fn zero_elapsed_time_has_no_hashrate() {
+		new_test_ext().execute_with(|| {
+			produce_blocks(&[1000, 1000, 1000, 1000]);
+
+			assert_eq!(Wtema::average_solvetime(3), Some(0));
+			assert_eq!(Wtema::network_hashrate_estimate(3), None);
+		});
+	}
+
+	#[test]
+	fn hashrate_is_work_over_elapsed_time() {
+		new_test_ext().execute_with(|| {
+			produce_blocks(&[1000, 2000, 3000, 4000]);
+
+			let work = (2..=4).map(|number| Wtema::difficulty_at(number).unwrap()).sum::<u128>();
+			assert_eq!(Wtema::network_hashrate_estimate(3), Some(work * 1000 / 3000));
+		});
 	}
 }
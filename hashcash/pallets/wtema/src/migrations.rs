@@ -0,0 +1,36 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+//! Storage migrations.
+
+use crate::*;
+
+use frame_support::{traits::OnRuntimeUpgrade, weights::Weight};
+use sp_runtime::traits::{One, Zero};
+
+/// Seeds [`RecentBlocks`] from [`MostRecentTimestamp`] on chains started before the pallet kept
+/// recent blocks, so that the first solvetime after the upgrade is measured as before.
+///
+/// The anchor of ASERT is the first block after the upgrade.
+pub struct SeedRecentBlocks<T>(PhantomData<T>);
+
+impl<T: Config> OnRuntimeUpgrade for SeedRecentBlocks<T> {
+	fn on_runtime_upgrade() -> Weight {
+		let weight = T::DbWeight::get().reads(2);
+		let number = frame_system::Pallet::<T>::block_number();
+		if number.is_zero() || !RecentBlocks::<T>::get().is_empty() {
+			return weight;
+		}
+
+		// The difficulty of the previous block is not kept, and no algorithm reads the difficulty
+		// of the oldest recent block.
+		let block = BlockInfo {
+			number: number - One::one(),
+			timestamp: MostRecentTimestamp::<T>::get(),
+			difficulty: Difficulty::<T>::get(),
+		};
+		RecentBlocks::<T>::put(BoundedVec::truncate_from(Vec::from([block])));
+
+		weight.saturating_add(T::DbWeight::get().reads_writes(2, 1))
+	}
+}
@@ -5,21 +5,55 @@ use crate::preludes::*;
 
 #[cfg(feature = "std")]
 use hashcash::primitives::core::{AccountId, Difficulty};
-#[cfg(feature = "std")]
-use substrate::codec::Decode;
 use substrate::{
-	codec::Encode,
+	codec::{Codec, Decode, Encode},
 	primitives::{
 		inherents::{self, InherentData, InherentIdentifier, IsFatalError},
-		std::collections::btree_map::BTreeMap,
+		runtime::traits::Zero,
+		std::{collections::btree_map::BTreeMap, vec::Vec},
 	},
 };
 
 pub const INHERENT_IDENTIFIER: InherentIdentifier = *b"coinbase";
 
-#[derive(Encode, sp_runtime::RuntimeDebug)]
+/// Error of checking the coinbase inherent of a block.
+#[derive(Encode, PartialEq, Eq, sp_runtime::RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(Decode, thiserror::Error))]
-pub enum InherentError {}
+pub enum InherentError {
+	/// The block has no coinbase.
+	#[cfg_attr(feature = "std", error("Block has no coinbase"))]
+	MissingCoinbase,
+	/// The coinbase has more reward splits than the runtime allows.
+	#[cfg_attr(
+		feature = "std",
+		error("Coinbase has {splits} reward splits, exceeding the maximum of {max}")
+	)]
+	TooManySplits { splits: u32, max: u32 },
+	/// The reward splits of the coinbase do not add up to the block reward.
+	#[cfg_attr(
+		feature = "std",
+		error("Coinbase reward splits add up to {actual} instead of the block reward {expected}")
+	)]
+	SumMismatch { expected: u128, actual: u128 },
+	/// The share data contains no shares.
+	#[cfg_attr(feature = "std", error("Coinbase share data contains no shares"))]
+	NoShares,
+	/// A share of the share data has zero weight.
+	#[cfg_attr(feature = "std", error("Coinbase share data contains a share of zero weight"))]
+	ZeroWeight,
+	/// The share data cannot be decoded.
+	#[cfg_attr(feature = "std", error("Coinbase share data is not correctly encoded"))]
+	InvalidShareData,
+	/// The reward splits of the coinbase do not follow the share data.
+	#[cfg_attr(feature = "std", error("Coinbase reward splits do not match the share data"))]
+	SplitMismatch,
+	/// A reward split of the coinbase has zero value.
+	#[cfg_attr(feature = "std", error("Coinbase has a reward split of zero value"))]
+	ZeroSplit,
+	/// An account has more than one reward split in the coinbase.
+	#[cfg_attr(feature = "std", error("Coinbase has more than one reward split for an account"))]
+	DuplicateSplit,
+}
 
 impl IsFatalError for InherentError {
 	fn is_fatal_error(&self) -> bool {
@@ -29,18 +63,81 @@ impl IsFatalError for InherentError {
 
 pub type InherentType<AccountId, Weight> = BTreeMap<AccountId, Weight>;
 
+/// Checks that the block reward can be split among `shares`.
+///
+/// Shares of zero weight are rejected rather than dropped, so that a miner whose shares are
+/// mistakenly weighed as zero is noticed before the block is mined.
+pub fn validate_shares<AccountId, Weight: Zero>(
+	shares: &InherentType<AccountId, Weight>,
+) -> Result<(), InherentError> {
+	if shares.is_empty() {
+		return Err(InherentError::NoShares)
+	}
+	if shares.values().any(Zero::is_zero) {
+		return Err(InherentError::ZeroWeight)
+	}
+	Ok(())
+}
+
+/// Coinbase rewards of an account which are locked until they mature.
+#[derive(
+	Clone, PartialEq, Eq, Default, Encode, Decode, scale_info::TypeInfo, sp_runtime::RuntimeDebug,
+)]
+pub struct LockedRewards<BlockNumber, Balance> {
+	/// Total locked amount.
+	pub locked: Balance,
+	/// Maturing amounts with the heights at which they unlock, in ascending order of the height.
+	pub maturing: Vec<(BlockNumber, Balance)>,
+}
+
+substrate::primitives::api::decl_runtime_apis! {
+	/// API to query the emission schedule of block rewards.
+	pub trait EmissionApi<BlockNumber, Balance>
+	where
+		BlockNumber: Codec,
+		Balance: Codec,
+	{
+		/// Returns the block reward of the block at `height`.
+		fn block_reward(height: BlockNumber) -> Balance;
+
+		/// Returns the total issuance projected at block `height` from the emission schedule.
+		fn projected_issuance(height: BlockNumber) -> Balance;
+	}
+
+	/// API to query the coinbase rewards of accounts.
+	pub trait CoinbaseApi<AccountId, BlockNumber, Balance>
+	where
+		AccountId: Codec,
+		BlockNumber: Codec,
+		Balance: Codec,
+	{
+		/// Returns the locked and maturing coinbase rewards of `account`.
+		fn locked_rewards(account: AccountId) -> LockedRewards<BlockNumber, Balance>;
+	}
+}
+
 #[cfg(feature = "std")]
 pub type InherentTypeImpl = BTreeMap<AccountId, Difficulty>;
 
+/// Inherent data provider of the share data of the authored block.
+///
+/// Invalid share data fails block production with the [`InherentError`], as the runtime cannot
+/// create a coinbase from it.
 #[cfg(feature = "std")]
 pub struct InherentDataProvider {
-	pub author: AccountId,
+	pub shares: InherentTypeImpl,
 }
 
 #[cfg(feature = "std")]
 impl InherentDataProvider {
+	/// Creates a provider paying the whole block reward to `author`.
 	pub fn new(author: AccountId) -> Self {
-		Self { author }
+		Self::with_shares(InherentTypeImpl::from([(author, 1 as Difficulty)]))
+	}
+
+	/// Creates a provider splitting the block reward among `shares`.
+	pub fn with_shares(shares: InherentTypeImpl) -> Self {
+		Self { shares }
 	}
 }
 
@@ -51,17 +148,51 @@ impl inherents::InherentDataProvider for InherentDataProvider {
 		&self,
 		inherent_data: &mut InherentData,
 	) -> Result<(), inherents::Error> {
-		inherent_data.put_data(
-			INHERENT_IDENTIFIER,
-			&InherentTypeImpl::from([(self.author.clone(), 1 as Difficulty)]),
-		)
+		validate_shares(&self.shares).map_err(|e| inherents::Error::Application(Box::new(e)))?;
+		inherent_data.put_data(INHERENT_IDENTIFIER, &self.shares)
 	}
 
 	async fn try_handle_error(
 		&self,
-		_identifier: &InherentIdentifier,
-		_error: &[u8],
+		identifier: &InherentIdentifier,
+		error: &[u8],
 	) -> Option<Result<(), inherents::Error>> {
-		None
+		handle_error(identifier, error)
 	}
 }
+
+/// Inherent data provider for verifying imported blocks.
+///
+/// It provides no share data, which only the author of a block knows, but decodes the coinbase
+/// errors of `check_inherents`.
+#[cfg(feature = "std")]
+pub struct ErrorHandler;
+
+#[cfg(feature = "std")]
+#[async_trait::async_trait]
+impl inherents::InherentDataProvider for ErrorHandler {
+	async fn provide_inherent_data(&self, _: &mut InherentData) -> Result<(), inherents::Error> {
+		Ok(())
+	}
+
+	async fn try_handle_error(
+		&self,
+		identifier: &InherentIdentifier,
+		error: &[u8],
+	) -> Option<Result<(), inherents::Error>> {
+		handle_error(identifier, error)
+	}
+}
+
+#[cfg(feature = "std")]
+fn handle_error(
+	identifier: &InherentIdentifier,
+	mut error: &[u8],
+) -> Option<Result<(), inherents::Error>> {
+	if *identifier != INHERENT_IDENTIFIER {
+		return None
+	}
+
+	let error = InherentError::decode(&mut error).ok()?;
+	Some(Err(inherents::Error::Application(Box::new(error))))
+}
@@ -0,0 +1,26 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::preludes::*;
+
+use substrate::codec::Codec;
+
+substrate::primitives::api::decl_runtime_apis! {
+	/// API to query the difficulty history of recent blocks.
+	pub trait DifficultyHistoryApi<BlockNumber, Moment, Difficulty>
+	where
+		BlockNumber: Codec,
+		Moment: Codec,
+		Difficulty: Codec,
+	{
+		/// Returns the difficulty of a block, if it is in the history.
+		fn difficulty_at(number: BlockNumber) -> Option<Difficulty>;
+
+		/// Returns the average solvetime of the last `window` blocks in milliseconds.
+		fn average_solvetime(window: u32) -> Option<Moment>;
+
+		/// Returns the network hashrate in hashes per second, estimated from the last `window`
+		/// blocks.
+		fn network_hashrate_estimate(window: u32) -> Option<Difficulty>;
+	}
+}
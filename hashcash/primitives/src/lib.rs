@@ -7,5 +7,6 @@
 mod preludes;
 
 pub mod coinbase;
+pub mod difficulty;
 
 pub use hashcash_primitives_core as core;
@@ -9,7 +9,9 @@ pub mod hashcash {
 pub mod substrate {
 	pub use parity_scale_codec as codec;
 	pub mod primitives {
+		pub use sp_api as api;
 		pub use sp_inherents as inherents;
+		pub use sp_runtime as runtime;
 		pub use sp_runtime::sp_std as std;
 	}
 }
@@ -2,9 +2,10 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use hashcash_randomx_sys as sys;
-use std::sync::Arc;
+use std::{ops::Range, sync::Arc};
 
-const RANDOMX_HASH_SIZE: usize = sys::RANDOMX_HASH_SIZE as usize;
+pub const RANDOMX_HASH_SIZE: usize = sys::RANDOMX_HASH_SIZE as usize;
+pub const RANDOMX_DATASET_ITEM_SIZE: usize = sys::RANDOMX_DATASET_ITEM_SIZE as usize;
 
 bitflags::bitflags! {
 	#[derive(Clone, Copy)]
@@ -86,19 +87,89 @@ impl RandomXDataset {
 		}
 	}
 
+	/// Returns the number of items contained in a dataset.
+	pub fn item_count() -> u64 {
+		unsafe { sys::randomx_dataset_item_count() as u64 }
+	}
+
 	/// Initializes RandomX dataset items.
 	pub fn init(&mut self, cache: &RandomXCache) {
-		unsafe {
-			let item_count = sys::randomx_dataset_item_count();
+		self.init_items(cache, 0..Self::item_count());
+	}
+
+	/// Initializes RandomX dataset items, splitting them evenly across the given number of threads.
+	pub fn init_parallel(&mut self, cache: &RandomXCache, threads: usize) {
+		let item_count = Self::item_count();
+		let chunk_size = item_count.div_ceil(threads.max(1) as u64).max(1);
+		let dataset = DatasetPointer(self.pointer);
+
+		std::thread::scope(|scope| {
+			for start in (0..item_count).step_by(chunk_size as usize) {
+				let count = chunk_size.min(item_count - start);
+				scope.spawn(move || unsafe {
+					sys::randomx_init_dataset(dataset.get(), cache.pointer, start as _, count as _);
+				});
+			}
+		});
+	}
 
-			sys::randomx_init_dataset(self.pointer, cache.pointer, 0, item_count);
+	/// Initializes the given range of dataset items.
+	///
+	/// Disjoint ranges can be initialized independently to build the dataset in parts.
+	pub fn init_items(&mut self, cache: &RandomXCache, items: Range<u64>) {
+		assert!(items.start <= items.end && items.end <= Self::item_count());
+
+		unsafe {
+			sys::randomx_init_dataset(
+				self.pointer,
+				cache.pointer,
+				items.start as _,
+				(items.end - items.start) as _,
+			);
 		}
 	}
+
+	/// Returns the memory of the given range of dataset items.
+	///
+	/// # Safety
+	///
+	/// Every item in `items` must have been initialized by [`Self::init`], [`Self::init_parallel`]
+	/// or [`Self::init_items`]. Freshly allocated dataset memory is uninitialized.
+	pub unsafe fn items(&self, items: Range<u64>) -> &[u8] {
+		assert!(items.start <= items.end && items.end <= Self::item_count());
+
+		let memory = sys::randomx_get_dataset_memory(self.pointer) as *const u8;
+		std::slice::from_raw_parts(
+			memory.add(items.start as usize * RANDOMX_DATASET_ITEM_SIZE),
+			(items.end - items.start) as usize * RANDOMX_DATASET_ITEM_SIZE,
+		)
+	}
+
+	/// Returns the dataset memory.
+	///
+	/// # Safety
+	///
+	/// The whole dataset must have been initialized, see [`Self::items`].
+	pub unsafe fn memory(&self) -> &[u8] {
+		self.items(0..Self::item_count())
+	}
 }
 
 unsafe impl Send for RandomXDataset {}
 unsafe impl Sync for RandomXDataset {}
 
+/// A dataset pointer shared by the threads initializing disjoint ranges of items.
+#[derive(Clone, Copy)]
+struct DatasetPointer(*mut sys::randomx_dataset);
+
+impl DatasetPointer {
+	fn get(self) -> *mut sys::randomx_dataset {
+		self.0
+	}
+}
+
+unsafe impl Send for DatasetPointer {}
+
 impl Drop for RandomXDataset {
 	fn drop(&mut self) {
 		unsafe {
@@ -203,8 +274,29 @@ impl RandomXVm {
 
 		output
 	}
+
+	/// Returns an iterator calculating the hash of `input(nonce)` for each of the given nonces.
+	///
+	/// Hashes are pipelined, so the calculation of the next hash begins before the previous one is
+	/// returned.
+	pub fn hashes<I, F>(&mut self, nonces: I, input: F) -> Hashes<'_, I::IntoIter, F, fn() -> bool>
+	where
+		I: IntoIterator,
+		F: FnMut(&I::Item) -> Vec<u8>,
+	{
+		Hashes {
+			vm: self,
+			nonces: nonces.into_iter(),
+			input,
+			is_cancelled: || false,
+			pending: None,
+		}
+	}
 }
 
+// A VM must not be used from multiple threads at once, which is already prevented by `&mut self`.
+unsafe impl Send for RandomXVm {}
+
 impl Drop for RandomXVm {
 	fn drop(&mut self) {
 		unsafe {
@@ -213,6 +305,139 @@ impl Drop for RandomXVm {
 	}
 }
 
+/// Calculates a RandomX hash value with a light VM initialized by the given key.
+///
+/// Initializing the cache takes much longer than hashing, so keep a [`RandomXVm`] to calculate
+/// more than one hash with the same key.
+pub fn calculate_hash_with_key(
+	flags: RandomXFlags,
+	key: &[u8],
+	input: &[u8],
+) -> Result<[u8; RANDOMX_HASH_SIZE], Error> {
+	let mut cache = RandomXCache::new(flags)?;
+	cache.init(key);
+	let mut vm = RandomXVm::new(flags, Some(Arc::new(cache)), None)?;
+
+	Ok(vm.calculate_hash(input))
+}
+
+/// Calculates a commitment to the input and its RandomX hash value.
+///
+/// The commitment can be verified without a VM, given the input and the hash.
+pub fn calculate_commitment(
+	input: &[u8],
+	hash: &[u8; RANDOMX_HASH_SIZE],
+) -> [u8; RANDOMX_HASH_SIZE] {
+	let mut output = [0u8; RANDOMX_HASH_SIZE];
+
+	unsafe {
+		sys::randomx_calculate_commitment(
+			input.as_ptr() as *const _,
+			input.len(),
+			hash.as_ptr() as *const _,
+			output.as_mut_ptr() as *mut _,
+		);
+	}
+
+	output
+}
+
+/// A pipelined iterator yielding `(nonce, hash)` pairs, created by [`RandomXVm::hashes`].
+pub struct Hashes<'a, I: Iterator, F, C> {
+	vm: &'a mut RandomXVm,
+	nonces: I,
+	input: F,
+	is_cancelled: C,
+	pending: Option<I::Item>,
+}
+
+impl<'a, I, F, C> Hashes<'a, I, F, C>
+where
+	I: Iterator,
+	F: FnMut(&I::Item) -> Vec<u8>,
+	C: FnMut() -> bool,
+{
+	/// Stops the iteration as soon as `is_cancelled` returns true.
+	///
+	/// It is checked before every hash, and the hash being calculated at that time is discarded.
+	pub fn cancel_when<T>(self, is_cancelled: T) -> Hashes<'a, I, F, T>
+	where
+		T: FnMut() -> bool,
+	{
+		let Self { vm, nonces, input, pending, .. } = self;
+		Hashes { vm, nonces, input, is_cancelled, pending }
+	}
+}
+
+impl<'a, I, F, C> Iterator for Hashes<'a, I, F, C>
+where
+	I: Iterator,
+	F: FnMut(&I::Item) -> Vec<u8>,
+	C: FnMut() -> bool,
+{
+	type Item = (I::Item, [u8; RANDOMX_HASH_SIZE]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		if (self.is_cancelled)() {
+			self.pending = None;
+			return None;
+		}
+
+		let pending = match self.pending.take() {
+			Some(pending) => pending,
+			None => {
+				let nonce = self.nonces.next()?;
+				self.vm.calculate_hash_first(&(self.input)(&nonce));
+				nonce
+			},
+		};
+
+		let hash = match self.nonces.next() {
+			Some(nonce) => {
+				let hash = self.vm.calculate_hash_next(&(self.input)(&nonce));
+				self.pending = Some(nonce);
+				hash
+			},
+			None => self.vm.calculate_hash_last(),
+		};
+		Some((pending, hash))
+	}
+}
+
+/// Calculates the hash of `input(nonce)` for each nonce in the range, using all the given VMs in
+/// parallel.
+///
+/// Each VM takes an interleaved share of the range, and the results are returned in nonce order.
+pub fn calculate_hashes<F>(
+	vms: &mut [RandomXVm],
+	nonces: Range<u64>,
+	input: F,
+) -> Vec<(u64, [u8; RANDOMX_HASH_SIZE])>
+where
+	F: Fn(u64) -> Vec<u8> + Sync,
+{
+	let step = vms.len().max(1);
+	let input = &input;
+
+	let mut hashes = std::thread::scope(|scope| {
+		let handles = vms
+			.iter_mut()
+			.enumerate()
+			.map(|(index, vm)| {
+				let nonces = nonces.clone().skip(index).step_by(step);
+				scope.spawn(move || vm.hashes(nonces, |nonce| input(*nonce)).collect::<Vec<_>>())
+			})
+			.collect::<Vec<_>>();
+
+		handles
+			.into_iter()
+			.flat_map(|handle| handle.join().expect("Hashing thread panicked"))
+			.collect::<Vec<_>>()
+	});
+	hashes.sort_unstable_by_key(|(nonce, _)| *nonce);
+	hashes
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -253,6 +478,63 @@ mod tests {
 		));
 	}
 
+	#[test]
+	fn hash_with_key() {
+		let hash =
+			calculate_hash_with_key(RandomXFlags::default(), b"test key 000", b"This is a test")
+				.expect("Failed to calculate hash");
+		assert!(equals_hex(
+			&hash,
+			"639183aae1bf4c9a35884cb46b09cad9175f04efd7684e7262a0ac1c2f0b4e3f"
+		));
+	}
+
+	#[test]
+	fn commitment() {
+		let hash =
+			calculate_hash_with_key(RandomXFlags::default(), b"test key 000", b"This is a test")
+				.expect("Failed to calculate hash");
+		let commitment = calculate_commitment(b"This is a test", &hash);
+		assert!(equals_hex(
+			&commitment,
+			"d53ccf348b75291b7be76f0a7ac8208bbced734b912f6fca60539ab6f86be919"
+		));
+	}
+
+	#[test]
+	fn dataset_items() {
+		let flags = RandomXFlags::default();
+		let mut cache = RandomXCache::new(flags).expect("Failed to allocate cache");
+		cache.init(b"test key 000");
+		let mut dataset = RandomXDataset::new(flags).expect("Failed to allocate dataset");
+
+		for (item, expected) in [
+			(0, 0x680588a85ae222db),
+			(10000000, 0x7943a1f6186ffb72),
+			(20000000, 0x9035244d718095e1),
+			(30000000, 0x145a5091f7853099),
+		] {
+			dataset.init_items(&cache, item..item + 1);
+			let word = unsafe { dataset.items(item..item + 1) }[..8].try_into().unwrap();
+			assert_eq!(u64::from_le_bytes(word), expected);
+		}
+	}
+
+	#[test]
+	fn init_parallel() {
+		let flags = RandomXFlags::default();
+		let mut cache = RandomXCache::new(flags).expect("Failed to allocate cache");
+		cache.init(b"test key 000");
+
+		let mut dataset = RandomXDataset::new(flags).expect("Failed to allocate dataset");
+		dataset.init(&cache);
+		for threads in [3, 8] {
+			let mut parallel = RandomXDataset::new(flags).expect("Failed to allocate dataset");
+			parallel.init_parallel(&cache, threads);
+			assert!(unsafe { dataset.memory() == parallel.memory() });
+		}
+	}
+
 	#[test]
 	fn reinit_cache() {
 		let flags = RandomXFlags::default();
@@ -279,6 +561,75 @@ mod tests {
 		));
 	}
 
+	fn light_vm_with_key(key: &[u8]) -> RandomXVm {
+		let flags = RandomXFlags::default();
+		let mut cache = RandomXCache::new(flags).expect("Failed to allocate cache");
+		cache.init(key);
+		RandomXVm::new(flags, Some(Arc::new(cache)), None).expect("Failed to create VM")
+	}
+
+	fn input_of(template: &[u8], nonce: u64) -> Vec<u8> {
+		[template, &nonce.to_le_bytes()[..]].concat()
+	}
+
+	proptest::proptest! {
+		#![proptest_config(proptest::prelude::ProptestConfig::with_cases(16))]
+
+		#[test]
+		fn hashes_match_calculate_hash(
+			template in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..128),
+			start in proptest::prelude::any::<u64>(),
+			count in 0u64..8,
+		) {
+			let mut vm = light_vm_with_key(b"test key 000");
+			let nonces = (0..count).map(|i| start.wrapping_add(i)).collect::<Vec<_>>();
+
+			let hashes = vm
+				.hashes(nonces.clone(), |nonce| input_of(&template, *nonce))
+				.collect::<Vec<_>>();
+			let expected = nonces
+				.into_iter()
+				.map(|nonce| (nonce, vm.calculate_hash(&input_of(&template, nonce))))
+				.collect::<Vec<_>>();
+			proptest::prop_assert_eq!(hashes, expected);
+		}
+
+		#[test]
+		fn calculate_hashes_matches_calculate_hash(
+			template in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..128),
+			start in 0u64..u64::MAX / 2,
+			count in 0u64..8,
+			vm_count in 1usize..4,
+		) {
+			let mut vms =
+				(0..vm_count).map(|_| light_vm_with_key(b"test key 000")).collect::<Vec<_>>();
+
+			let nonces = start..start + count;
+			let hashes = calculate_hashes(&mut vms, nonces, |nonce| input_of(&template, nonce));
+			let expected = (start..start + count)
+				.map(|nonce| (nonce, vms[0].calculate_hash(&input_of(&template, nonce))))
+				.collect::<Vec<_>>();
+			proptest::prop_assert_eq!(hashes, expected);
+		}
+	}
+
+	#[test]
+	fn cancel_hashes() {
+		let mut vm = light_vm_with_key(b"test key 000");
+		let mut count = 0;
+		let hashes = vm
+			.hashes(0u64.., |nonce| input_of(b"This is a test", *nonce))
+			.cancel_when(|| {
+				count += 1;
+				count > 3
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(hashes.len(), 3);
+
+		// The VM is still usable after the pipeline was abandoned.
+		assert_eq!(hashes[2].1, vm.calculate_hash(&input_of(b"This is a test", 2)));
+	}
+
 	#[test]
 	fn calculate_multiple_hashes() {
 		let flags = RandomXFlags::default();
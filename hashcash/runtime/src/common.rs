@@ -21,7 +21,7 @@ pub type Executive = substrate::frames::executive::Executive<
 
 pub type Header = generic::Header<BlockNumber, BlakeTwo256>;
 
-pub type Migrations = ();
+pub type Migrations = (hashcash::pallets::wtema::migrations::SeedRecentBlocks<Runtime>,);
 
 pub type SignedExtra = (
 	substrate::frames::system::CheckNonZeroSender<Runtime>,
@@ -4,31 +4,29 @@
 use crate::*;
 
 use hashcash::{
-	pallets::coinbase::{BalanceOf, EmissionCurve},
+	pallets::coinbase::emission::ExponentialDecay,
 	primitives::core::{units::DOLLARS, BlockNumber},
 };
-
-const TARGET_ISSUANCE: Balance = 1_000_000_000 * DOLLARS;
+use substrate::primitives::runtime::Perquintill;
 
 parameter_types! {
 	pub const MaxRewardSplits: u32 = 1024;
 	pub const MaturationTime: BlockNumber = 60;
+	/// Supply emitted by block rewards.
+	///
+	/// Unlike the issuance target of the previous curve, genesis endowments do not count towards
+	/// this supply, so they are issued on top of it. The chain specs have no endowments.
+	pub const EmissionSupply: Balance = 1_000_000_000 * DOLLARS;
+	/// Fraction of the remaining supply emitted by each block.
+	pub EmissionDecay: Perquintill = Perquintill::from_rational(1u64, 2u64 << 20);
 }
 
 impl hashcash::pallets::coinbase::Config for Runtime {
-	type EmissionCurve = HashcashEmissionCurve;
+	type RuntimeEvent = RuntimeEvent;
+	type EmissionCurve = ExponentialDecay<Self, EmissionSupply, EmissionDecay>;
 	type Currency = Balances;
 	type MaxRewardSplits = MaxRewardSplits;
 	type MaturationTime = MaturationTime;
 	type Difficulty = Difficulty;
-}
-
-pub struct HashcashEmissionCurve;
-
-impl EmissionCurve<Runtime> for HashcashEmissionCurve {
-	fn emit() -> BalanceOf<Runtime> {
-		let total_issuance = <Runtime as pallet_coinbase::Config>::Currency::total_issuance();
-
-		(TARGET_ISSUANCE - total_issuance) / ((2 as Balance) << 20)
-	}
+	type WeightInfo = hashcash::pallets::coinbase::weights::SubstrateWeight<Self>;
 }
@@ -12,6 +12,13 @@ const TARGET_BLOCK_TIME: Moment = 120 * SECONDS;
 parameter_types! {
 	/// Smoothing factor for difficulty adjustment.
 	pub const Filter: u32 = FILTER;
+	/// Bounds of a solvetime in target block times.
+	///
+	/// The lower bound is inert, as timestamps increase by at least `MinimumPeriod`. The upper
+	/// bound limits the difficulty drop after a single long solvetime.
+	pub const SolvetimeBounds: (i32, i32) = (-6, 6);
+	/// Number of blocks kept in the difficulty history, a week of blocks.
+	pub const HistorySize: u32 = 5040;
 	/// Desired block time in milliseconds.
 	pub const TargetBlockTime: Moment = TARGET_BLOCK_TIME;
 	/// Minimum difficulty to be adjusted according to block time changes.
@@ -22,7 +29,10 @@ parameter_types! {
 
 impl hashcash::pallets::wtema::Config for Runtime {
 	type Difficulty = Difficulty;
+	type Algorithm = hashcash::pallets::wtema::Wtema;
 	type Filter = Filter;
+	type SolvetimeBounds = SolvetimeBounds;
 	type TargetBlockTime = TargetBlockTime;
 	type MinDifficulty = MinDifficulty;
+	type HistorySize = HistorySize;
 }
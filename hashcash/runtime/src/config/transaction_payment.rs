@@ -3,7 +3,7 @@
 
 use crate::*;
 
-use hashcash::primitives::core::units::CENTS;
+use hashcash::{pallets::coinbase::DealWithFees, primitives::core::units::CENTS};
 use smallvec::smallvec;
 use substrate::{
 	frames::support::weights::{
@@ -51,7 +51,7 @@ impl WeightToFeePolynomial for WeightToFee {
 impl substrate::pallets::transaction_payment::Config for Runtime {
 	type FeeMultiplierUpdate = SlowAdjustingFeeUpdate<Self>;
 	type LengthToFee = ConstantMultiplier<Balance, TransactionByteFee>;
-	type OnChargeTransaction = CurrencyAdapter<Balances, () /* TODO: DealWithFees */>;
+	type OnChargeTransaction = CurrencyAdapter<Balances, DealWithFees<Self>>;
 	type OperationalFeeMultiplier = OperationalFeeMultiplier;
 	type RuntimeEvent = RuntimeEvent;
 	type WeightToFee = WeightToFee;
@@ -18,7 +18,10 @@ mod version;
 pub use common::*;
 pub use version::*;
 
-use hashcash::primitives::core::{AccountId, AccountNonce, Balance, Difficulty, Moment};
+use hashcash::primitives::{
+	coinbase::LockedRewards,
+	core::{AccountId, AccountNonce, Balance, BlockNumber, Difficulty, Moment},
+};
 use substrate::{
 	frames::support::{
 		construct_runtime,
@@ -191,6 +194,40 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl hashcash::primitives::difficulty::DifficultyHistoryApi<Block, BlockNumber, Moment, Difficulty>
+		for Runtime
+	{
+		fn difficulty_at(number: BlockNumber) -> Option<Difficulty> {
+			DifficultyAdjustment::difficulty_at(number)
+		}
+
+		fn average_solvetime(window: u32) -> Option<Moment> {
+			DifficultyAdjustment::average_solvetime(window)
+		}
+
+		fn network_hashrate_estimate(window: u32) -> Option<Difficulty> {
+			DifficultyAdjustment::network_hashrate_estimate(window)
+		}
+	}
+
+	impl hashcash::primitives::coinbase::EmissionApi<Block, BlockNumber, Balance> for Runtime {
+		fn block_reward(height: BlockNumber) -> Balance {
+			Coinbase::block_reward(height)
+		}
+
+		fn projected_issuance(height: BlockNumber) -> Balance {
+			Coinbase::projected_issuance(height)
+		}
+	}
+
+	impl hashcash::primitives::coinbase::CoinbaseApi<Block, AccountId, BlockNumber, Balance>
+		for Runtime
+	{
+		fn locked_rewards(account: AccountId) -> LockedRewards<BlockNumber, Balance> {
+			Coinbase::locked_rewards(&account)
+		}
+	}
+
 	impl substrate::primitives::genesis_builder::GenesisBuilder<Block> for Runtime {
 		fn create_default_config() -> Vec<u8> {
 			create_default_config::<RuntimeGenesisConfig>()
@@ -270,6 +307,7 @@ mod benches {
 		[frame_benchmarking, BaselineBench::<Runtime>]
 		[frame_system, SystemBench::<Runtime>]
 		[pallet_balances, Balances]
+		[pallet_coinbase, Coinbase]
 		[pallet_timestamp, Timestamp]
 	);
 }
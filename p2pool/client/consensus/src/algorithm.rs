@@ -54,6 +54,30 @@ where
 			.map_err(|e| Error::Client(e.into()))
 	}
 
+	fn break_tie(
+		&self,
+		own_pre_hash: &Hash,
+		own_seal: &Seal,
+		new_pre_hash: &Hash,
+		new_seal: &Seal,
+	) -> bool {
+		consensus::break_tie(own_pre_hash, own_seal, new_pre_hash, new_seal)
+	}
+
+	fn preliminary_verify(
+		&self,
+		_header: &<Block as BlockT>::Header,
+		_pre_hash: &Hash,
+		pre_digest: Option<&[u8]>,
+		seal: &Seal,
+	) -> Result<Option<bool>, Error<Block>> {
+		let decoded = pre_digest
+			.map(|v| <(AccountId, MinerData)>::decode(&mut &v[..]).is_ok())
+			.unwrap_or(false);
+
+		Ok(Some(decoded && consensus::Seal::decode(&mut &seal[..]).is_ok()))
+	}
+
 	fn verify(
 		&self,
 		_parent: &BlockId,
@@ -5,7 +5,7 @@ use crate::preludes::*;
 
 use futures::stream::StreamExt;
 use hashcash::{
-	client::api::BlockSubmitParams,
+	client::api::{BlockSubmitParams, BlockSubmitResult},
 	primitives::core::{Bytes, H256},
 };
 use jsonrpsee::{
@@ -53,13 +53,14 @@ impl BlockSubmitWorker {
 	async fn submit_block(&mut self, block: Block, seal: Vec<u8>) {
 		match self
 			.rpc_client
-			.request::<H256, ArrayParams>(
-				"miner_submitBlock",
+			.request::<BlockSubmitResult<H256>, ArrayParams>(
+				"miner_submitBlockWithStatus",
 				rpc_params!(Bytes::from((block, seal).encode())),
 			)
 			.await
 		{
-			Ok(hash) => log::info!(target: LOG_TARGET, "📡 Block submitted: {}", hash),
+			Ok(BlockSubmitResult { hash, status }) =>
+				log::info!(target: LOG_TARGET, "📡 Block submitted: {} ({:?})", hash, status),
 			Err(err) => log::error!(target: LOG_TARGET, "Failed to submit block: {}", err),
 		}
 	}
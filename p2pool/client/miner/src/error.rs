@@ -12,6 +12,10 @@ pub enum MinerDataError {
 	#[error(transparent)]
 	HttpClient(jsonrpsee::core::client::Error),
 	#[error(transparent)]
+	WsClient(jsonrpsee::core::client::Error),
+	#[error("Job subscription closed")]
+	SubscriptionClosed,
+	#[error(transparent)]
 	Blockchain(substrate::primitives::blockchain::Error),
 	#[error("{0}")]
 	Other(String),
@@ -3,14 +3,19 @@
 
 use crate::{error::*, preludes::*};
 
+use futures::FutureExt;
 use hashcash::{
 	client::api::MinerData,
 	primitives::core::{AccountId, Difficulty},
 };
 use jsonrpsee::{
-	core::{client::ClientT, params::ArrayParams},
+	core::{
+		client::{ClientT, Subscription, SubscriptionClientT},
+		params::ArrayParams,
+	},
 	http_client::{HttpClient, HttpClientBuilder},
 	rpc_params,
+	ws_client::{WsClient, WsClientBuilder},
 };
 use p2pool::client::consensus::P2POOL_AUX_PREFIX;
 use std::{collections::BTreeMap, sync::Arc};
@@ -29,9 +34,123 @@ use substrate::{
 
 const LOG_TARGET: &str = "miner-data";
 
+#[derive(Clone)]
+enum RpcClient {
+	/// Polls `miner_getMinerData` on every request.
+	Http(HttpClient),
+	/// Consumes jobs pushed by `miner_subscribeJobs`.
+	Ws(Arc<JobSubscription>),
+}
+
+struct JobSubscription {
+	url: String,
+	state: tokio::sync::Mutex<JobSubscriptionState>,
+}
+
+#[derive(Default)]
+struct JobSubscriptionState {
+	/// The connection, kept open across resubscriptions.
+	client: Option<WsClient>,
+	jobs: Option<Jobs>,
+}
+
+struct Jobs {
+	shares: Vec<(AccountId, Difficulty)>,
+	subscription: Subscription<MinerData>,
+	latest: Option<MinerData>,
+}
+
+impl JobSubscription {
+	fn new(url: String) -> Self {
+		Self { url, state: Default::default() }
+	}
+
+	/// Returns the latest job pushed for the given shares, resubscribing when the shares have
+	/// changed and reconnecting only when the connection has been lost.
+	async fn miner_data(
+		&self,
+		author: &AccountId,
+		shares: Vec<(AccountId, Difficulty)>,
+	) -> Result<MinerData, MinerDataError> {
+		let mut state = self.state.lock().await;
+		let state = &mut *state;
+
+		let client = match state.client.take() {
+			Some(client) if client.is_connected() => client,
+			_ => {
+				state.jobs = None;
+				self.connect().await?
+			},
+		};
+		let client = state.client.insert(client);
+
+		let jobs = match state.jobs.take() {
+			Some(jobs) if jobs.shares == shares => jobs,
+			// Dropping the previous subscription unsubscribes it.
+			_ => self.subscribe(client, author, shares).await?,
+		};
+		let jobs = state.jobs.insert(jobs);
+
+		match Self::latest(jobs).await {
+			Ok(miner_data) => Ok(miner_data),
+			Err(e) => {
+				state.jobs = None;
+				Err(e)
+			},
+		}
+	}
+
+	async fn latest(jobs: &mut Jobs) -> Result<MinerData, MinerDataError> {
+		// Drain the jobs pushed so far to get the newest one.
+		while let Some(next) = jobs.subscription.next().now_or_never() {
+			let miner_data = next.ok_or(MinerDataError::SubscriptionClosed)?;
+			jobs.latest = Some(miner_data.map_err(MinerDataError::WsClient)?);
+		}
+
+		match &jobs.latest {
+			Some(miner_data) => Ok(miner_data.clone()),
+			None => {
+				let miner_data =
+					jobs.subscription.next().await.ok_or(MinerDataError::SubscriptionClosed)?;
+				let miner_data = miner_data.map_err(MinerDataError::WsClient)?;
+				jobs.latest = Some(miner_data.clone());
+				Ok(miner_data)
+			},
+		}
+	}
+
+	async fn connect(&self) -> Result<WsClient, MinerDataError> {
+		let client = WsClientBuilder::default()
+			.build(&self.url)
+			.await
+			.map_err(MinerDataError::WsClient)?;
+		log::debug!(target: LOG_TARGET, "Connected to {}", self.url);
+		Ok(client)
+	}
+
+	async fn subscribe(
+		&self,
+		client: &WsClient,
+		author: &AccountId,
+		shares: Vec<(AccountId, Difficulty)>,
+	) -> Result<Jobs, MinerDataError> {
+		let subscription = client
+			.subscribe::<MinerData, ArrayParams>(
+				"miner_subscribeJobs",
+				rpc_params!(author.clone(), shares.clone()),
+				"miner_unsubscribeJobs",
+			)
+			.await
+			.map_err(MinerDataError::WsClient)?;
+		log::debug!(target: LOG_TARGET, "Subscribed to mining jobs: {}", self.url);
+
+		Ok(Jobs { shares, subscription, latest: None })
+	}
+}
+
 #[derive(Clone)]
 pub struct MinerDataProvider<B: Block, C> {
-	rpc_client: HttpClient,
+	rpc_client: RpcClient,
 	client: Arc<C>,
 	author: AccountId,
 	genesis_hash: B::Hash,
@@ -43,22 +162,24 @@ where
 	B: Block,
 	C: AuxStore + BlockchainEvents<B> + HeaderBackend<B> + 'static,
 {
+	/// Creates a provider that polls `mainchain_rpc`, or subscribes to `mainchain_ws` if given.
 	pub fn new(
 		mainchain_rpc: String,
+		mainchain_ws: Option<String>,
 		client: Arc<C>,
 		author: AccountId,
 		genesis_hash: B::Hash,
 		window_size: NumberFor<B>,
 	) -> Result<Self, MinerDataError> {
-		Ok(Self {
-			rpc_client: HttpClientBuilder::default()
-				.build(mainchain_rpc)
-				.map_err(MinerDataError::HttpClient)?,
-			client,
-			author,
-			genesis_hash,
-			window_size,
-		})
+		let rpc_client = match mainchain_ws {
+			Some(url) => RpcClient::Ws(Arc::new(JobSubscription::new(url))),
+			None => RpcClient::Http(
+				HttpClientBuilder::default()
+					.build(mainchain_rpc)
+					.map_err(MinerDataError::HttpClient)?,
+			),
+		};
+		Ok(Self { rpc_client, client, author, genesis_hash, window_size })
 	}
 
 	pub async fn miner_data(&self, best_hash: &B::Hash) -> Option<MinerData> {
@@ -73,13 +194,16 @@ where
 
 	async fn miner_data_inner(&self, best_hash: &B::Hash) -> Result<MinerData, MinerDataError> {
 		let shares = self.get_shares(best_hash).await?;
-		self.rpc_client
-			.request::<MinerData, ArrayParams>(
-				"miner_getMinerData",
-				rpc_params!(self.author.clone(), shares),
-			)
-			.await
-			.map_err(MinerDataError::HttpClient)
+		match &self.rpc_client {
+			RpcClient::Http(rpc_client) => rpc_client
+				.request::<MinerData, ArrayParams>(
+					"miner_getMinerData",
+					rpc_params!(self.author.clone(), shares),
+				)
+				.await
+				.map_err(MinerDataError::HttpClient),
+			RpcClient::Ws(subscription) => subscription.miner_data(&self.author, shares).await,
+		}
 	}
 
 	async fn get_shares(
@@ -26,6 +26,9 @@ pub struct CliOptions {
 	/// Specify the mainchain rpc endpoint for p2pool mining.
 	#[arg(long, value_name = "ADDR", default_value = "http://localhost:9944")]
 	pub mainchain_rpc: String,
+	/// Specify the mainchain websocket endpoint to subscribe to mining jobs instead of polling.
+	#[arg(long, value_name = "ADDR")]
+	pub mainchain_ws: Option<String>,
 	/// Account for block mining rewards.
 	#[arg(long)]
 	pub author: Option<String>,
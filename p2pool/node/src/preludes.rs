@@ -3,11 +3,14 @@
 
 pub mod hashcash {
 	pub mod client {
+		pub use hashcash_client_api as api;
 		pub mod miner {
 			pub use hashcash_client_miner_worker as worker;
 		}
+		pub use hashcash_client_rpc as rpc;
 	}
 	pub mod primitives {
+		pub use hashcash_primitives::difficulty;
 		pub use hashcash_primitives_core as core;
 	}
 }
@@ -3,11 +3,23 @@
 
 use crate::preludes::*;
 
-use hashcash::primitives::core::{opaque::Block, AccountId, AccountNonce};
+use hashcash::{
+	client::rpc::{
+		difficulty::{DifficultyApiServer, DifficultyHistory},
+		pow::{Pow, PowApiServer},
+	},
+	primitives::{
+		core::{opaque::Block, AccountId, AccountNonce, BlockNumber, Difficulty, Moment},
+		difficulty::DifficultyHistoryApi,
+	},
+};
 use jsonrpsee::RpcModule;
 use std::{error::Error, sync::Arc};
 use substrate::{
-	client::{rpc::api::DenyUnsafe, transaction_pool::api::TransactionPool},
+	client::{
+		consensus::pow::ReorgNotifications, rpc::api::DenyUnsafe,
+		transaction_pool::api::TransactionPool,
+	},
 	frames::system::rpc::AccountNonceApi,
 	primitives::{
 		api::ProvideRuntimeApi,
@@ -20,6 +32,7 @@ pub struct FullDeps<C, P> {
 	pub client: Arc<C>,
 	pub pool: Arc<P>,
 	pub deny_unsafe: DenyUnsafe,
+	pub reorgs: ReorgNotifications<Block>,
 }
 
 pub fn create_full<C, P>(
@@ -31,14 +44,17 @@ where
 	C: Send + Sync + 'static,
 	C::Api: AccountNonceApi<Block, AccountId, AccountNonce>,
 	C::Api: BlockBuilder<Block>,
+	C::Api: DifficultyHistoryApi<Block, BlockNumber, Moment, Difficulty>,
 	P: TransactionPool + 'static,
 {
 	use substrate::frames::system::rpc::{System, SystemApiServer};
 
 	let mut module = RpcModule::new(());
-	let FullDeps { client, pool, deny_unsafe } = deps;
+	let FullDeps { client, pool, deny_unsafe, reorgs } = deps;
 
 	module.merge(System::new(client.clone(), pool, deny_unsafe).into_rpc())?;
+	module.merge(DifficultyHistory::new(client).into_rpc())?;
+	module.merge(Pow::new(reorgs).into_rpc())?;
 
 	Ok(module)
 }
@@ -5,7 +5,7 @@ use crate::{cli::CliOptions, preludes::*};
 
 use futures::FutureExt;
 use hashcash::{
-	client::miner::worker::MiningWorker,
+	client::{api::timestamp::FutureTimeLimit, miner::worker::MiningWorker},
 	primitives::core::{opaque::Block, AccountId},
 };
 use p2pool::{
@@ -21,8 +21,11 @@ use substrate::{
 		api::Backend,
 		basic_authorship::ProposerFactory,
 		consensus::{
-			pow::{Error as PowError, ImportQueueParams, PowBlockImport, PowParams},
-			DefaultImportQueue, LongestChain,
+			pow::{
+				ChainLimits, Error as PowError, ImportQueueParams, PowBlockImport, PowImportQueue,
+				PowParams,
+			},
+			LongestChain,
 		},
 		executor::WasmExecutor,
 		network::config::FullNetworkConfiguration,
@@ -48,7 +51,7 @@ pub type Service = service::PartialComponents<
 	FullClient,
 	FullBackend,
 	FullSelectChain,
-	DefaultImportQueue<Block>,
+	PowImportQueue<Block>,
 	FullPool<Block, FullClient>,
 	(
 		PowBlockImport<
@@ -62,6 +65,9 @@ pub type Service = service::PartialComponents<
 	),
 >;
 
+/// Maximum time a block timestamp may be ahead of the local clock, half of the block time.
+const FUTURE_TIME_LIMIT: Duration = Duration::from_secs(5);
+
 pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 	let telemetry = config
 		.telemetry_endpoints
@@ -107,6 +113,9 @@ pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 		client.clone(),
 		select_chain.clone(),
 		algorithm.clone(),
+		// The sidechain has no checkpoints or reorg limit.
+		ChainLimits::default(),
+		config.prometheus_registry(),
 	);
 
 	let import_queue = substrate::client::consensus::pow::import_queue(ImportQueueParams {
@@ -115,10 +124,12 @@ pub fn new_partial(config: &Configuration) -> Result<Service, Error> {
 		client: client.clone(),
 		algorithm: algorithm.clone(),
 		create_inherent_data_providers: move |_, ()| async move {
-			Ok(TimestampInherentDataProvider::from_system_time())
+			Ok(FutureTimeLimit::from_system_time(FUTURE_TIME_LIMIT))
 		},
 		spawner: &task_manager.spawn_essential_handle(),
 		registry: config.prometheus_registry(),
+		// P2Pool seals are verified against the mainchain blocks they carry, not ahead of import.
+		precompute_threads: 0,
 	})?;
 
 	Ok(service::PartialComponents {
@@ -214,10 +225,15 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 	let rpc_extensions_builder = {
 		let client = client.clone();
 		let pool = transaction_pool.clone();
+		let reorgs = block_import.reorg_notifications();
 
 		Box::new(move |deny_unsafe, _| {
-			let deps =
-				crate::rpc::FullDeps { client: client.clone(), pool: pool.clone(), deny_unsafe };
+			let deps = crate::rpc::FullDeps {
+				client: client.clone(),
+				pool: pool.clone(),
+				deny_unsafe,
+				reorgs: reorgs.clone(),
+			};
 			crate::rpc::create_full(deps).map_err(Into::into)
 		})
 	};
@@ -243,6 +259,7 @@ pub fn new_full(config: Configuration, options: CliOptions) -> Result<TaskManage
 		let genesis_hash = client.chain_info().genesis_hash;
 		let provider = MinerDataProvider::new(
 			options.mainchain_rpc.clone(),
+			options.mainchain_ws.clone(),
 			client.clone(),
 			author.clone(),
 			genesis_hash,
@@ -18,7 +18,7 @@ mod version;
 pub use common::*;
 pub use version::*;
 
-use hashcash::primitives::core::{AccountId, AccountNonce, Difficulty, Moment};
+use hashcash::primitives::core::{AccountId, AccountNonce, BlockNumber, Difficulty, Moment};
 use substrate::{
 	frames::support::{
 		construct_runtime,
@@ -142,6 +142,22 @@ impl_runtime_apis! {
 		}
 	}
 
+	impl hashcash::primitives::difficulty::DifficultyHistoryApi<Block, BlockNumber, Moment, Difficulty>
+		for Runtime
+	{
+		fn difficulty_at(number: BlockNumber) -> Option<Difficulty> {
+			DifficultyAdjustment::difficulty_at(number)
+		}
+
+		fn average_solvetime(window: u32) -> Option<Moment> {
+			DifficultyAdjustment::average_solvetime(window)
+		}
+
+		fn network_hashrate_estimate(window: u32) -> Option<Difficulty> {
+			DifficultyAdjustment::network_hashrate_estimate(window)
+		}
+	}
+
 	impl substrate::primitives::genesis_builder::GenesisBuilder<Block> for Runtime {
 		fn create_default_config() -> Vec<u8> {
 			create_default_config::<RuntimeGenesisConfig>()
@@ -6,6 +6,7 @@ pub mod hashcash {
 		pub use pallet_wtema as wtema;
 	}
 	pub mod primitives {
+		pub use hashcash_primitives::difficulty;
 		pub use hashcash_primitives_core as core;
 	}
 }
@@ -14,7 +14,7 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	impl_name: create_runtime_str!("p2pool"),
 	authoring_version: 1,
 	// spec_version: MAJOR_MINOR_PATCH
-	spec_version: 000_001_000,
+	spec_version: 000_002_000,
 	impl_version: 1,
 	apis: RUNTIME_API_VERSIONS,
 	transaction_version: 1,
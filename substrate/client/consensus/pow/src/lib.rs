@@ -39,10 +39,15 @@
 //! as the storage, but it is not recommended as it won't work well with light
 //! clients.
 
+mod metrics;
+mod precompute;
+mod reorg;
 mod worker;
 
-pub use crate::worker::{
-	MiningBuild, MiningHandle, MiningMetadata, UntilImportedOrTimeout, Version,
+pub use crate::{
+	precompute::{PowImportQueue, WorkCache},
+	reorg::{ReorgEvent, ReorgNotifications},
+	worker::{MiningBuild, MiningHandle, MiningMetadata, UntilImportedOrTimeout, Version},
 };
 use futures::{Future, StreamExt};
 use log::*;
@@ -54,16 +59,16 @@ use sc_consensus::{
 };
 use sp_api::ProvideRuntimeApi;
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
-use sp_blockchain::HeaderBackend;
+use sp_blockchain::{lowest_common_ancestor, HashAndNumber, HeaderBackend, HeaderMetadata};
 use sp_consensus::{Environment, Error as ConsensusError, Proposer, SelectChain, SyncOracle};
 use sp_consensus_pow::{Seal, TotalDifficulty, POW_ENGINE_ID};
 use sp_inherents::{CreateInherentDataProviders, InherentDataProvider};
 use sp_runtime::{
 	generic::{BlockId, Digest, DigestItem},
-	traits::{Block as BlockT, Header as HeaderT},
-	ConsensusEngineId, RuntimeString,
+	traits::{Block as BlockT, Header as HeaderT, NumberFor, Saturating, UniqueSaturatedInto},
+	ConsensusEngineId, RuntimeString, SaturatedConversion,
 };
-use std::{cmp::Ordering, marker::PhantomData, sync::Arc, time::Duration};
+use std::{cmp::Ordering, collections::BTreeMap, marker::PhantomData, sync::Arc, time::Duration};
 use substrate_prometheus_endpoint::Registry;
 
 const LOG_TARGET: &str = "pow";
@@ -103,6 +108,12 @@ pub enum Error<B: BlockT> {
 	CheckInherentsUnknownError(sp_inherents::InherentIdentifier),
 	#[error("Multiple pre-runtime digests")]
 	MultiplePreRuntimeDigests,
+	#[error("Block #{0} does not match the checkpoint {1:?}")]
+	CheckpointMismatch(NumberFor<B>, B::Hash),
+	#[error("Reorg would revert the checkpoint at #{0}")]
+	CheckpointReverted(NumberFor<B>),
+	#[error("Reorg of {0} blocks exceeds the maximum reorg depth of {1}")]
+	ReorgTooDeep(NumberFor<B>, NumberFor<B>),
 	#[error(transparent)]
 	Client(sp_blockchain::Error),
 	#[error(transparent)]
@@ -179,12 +190,17 @@ pub trait PowAlgorithm<B: BlockT> {
 	/// This function will be called twice during the import process, so the implementation
 	/// should be properly cached.
 	fn difficulty(&self, parent: B::Hash) -> Result<Self::Difficulty, Error<B>>;
-	/// Verify that the seal is valid against given pre hash when parent block is not yet imported.
+	/// Verify that the seal is valid against given pre hash before the block is imported.
 	///
-	/// None means that preliminary verify is not available for this algorithm.
+	/// This is called from the import queue with the header without its seal, when the parent
+	/// block may not have been imported yet. Checks which need the parent state can only be made
+	/// if the parent is known. None means that preliminary verify is not available for this
+	/// algorithm or block, and the seal is only verified on import.
 	fn preliminary_verify(
 		&self,
+		_header: &B::Header,
 		_pre_hash: &B::Hash,
+		_pre_digest: Option<&[u8]>,
 		_seal: &Seal,
 	) -> Result<Option<bool>, Error<B>> {
 		Ok(None)
@@ -194,10 +210,31 @@ pub trait PowAlgorithm<B: BlockT> {
 	/// By default this chooses the earliest block seen. Using uniform tie
 	/// breaking algorithms will help to protect against selfish mining.
 	///
-	/// Returns if the new seal should be considered best block.
-	fn break_tie(&self, _own_seal: &Seal, _new_seal: &Seal) -> bool {
+	/// Both blocks are given by their pre hash and seal, since seals alone may be equal for
+	/// different blocks. Returns if the new seal should be considered best block.
+	fn break_tie(
+		&self,
+		_own_pre_hash: &B::Hash,
+		_own_seal: &Seal,
+		_new_pre_hash: &B::Hash,
+		_new_seal: &Seal,
+	) -> bool {
 		false
 	}
+	/// Compute the work of a seal before the parent block is imported, so that [`Self::verify`]
+	/// can reuse it.
+	///
+	/// The import queue calls this on worker threads with the header without its seal, and the
+	/// headers queued for import before it, oldest first. By default nothing is computed.
+	fn precompute(
+		&self,
+		_pre_hash: &B::Hash,
+		_seal: &Seal,
+		_header: &B::Header,
+		_queued: &[B::Header],
+	) -> Result<(), Error<B>> {
+		Ok(())
+	}
 	/// Verify that the difficulty is valid against given seal.
 	fn verify(
 		&self,
@@ -209,12 +246,30 @@ pub trait PowAlgorithm<B: BlockT> {
 	) -> Result<bool, Error<B>>;
 }
 
+/// Limits on the chains accepted by [`PowBlockImport`] regardless of their total difficulty.
+#[derive(Clone, Debug)]
+pub struct ChainLimits<B: BlockT> {
+	/// Hashes of the blocks every accepted chain must contain, by block number.
+	pub checkpoints: BTreeMap<NumberFor<B>, B::Hash>,
+	/// Maximum number of best chain blocks a reorg can revert. None means no limit.
+	pub max_reorg_depth: Option<NumberFor<B>>,
+}
+
+impl<B: BlockT> Default for ChainLimits<B> {
+	fn default() -> Self {
+		Self { checkpoints: BTreeMap::new(), max_reorg_depth: None }
+	}
+}
+
 /// A block importer for PoW.
-pub struct PowBlockImport<B, I, C, SC, A> {
+pub struct PowBlockImport<B: BlockT, I, C, SC, A> {
 	inner: I,
 	client: Arc<C>,
 	select_chain: SC,
 	algorithm: A,
+	limits: ChainLimits<B>,
+	metrics: Option<metrics::Metrics>,
+	reorgs: ReorgNotifications<B>,
 	_marker: PhantomData<B>,
 }
 
@@ -225,6 +280,9 @@ impl<B: BlockT, I: Clone, C, SC: Clone, A: Clone> Clone for PowBlockImport<B, I,
 			client: self.client.clone(),
 			select_chain: self.select_chain.clone(),
 			algorithm: self.algorithm.clone(),
+			limits: self.limits.clone(),
+			metrics: self.metrics.clone(),
+			reorgs: self.reorgs.clone(),
 			_marker: Default::default(),
 		}
 	}
@@ -240,8 +298,79 @@ where
 	A: PowAlgorithm<B>,
 {
 	/// Create a new block import suitable to be used in PoW
-	pub fn new(inner: I, client: Arc<C>, select_chain: SC, algorithm: A) -> Self {
-		Self { inner, client, select_chain, algorithm, _marker: Default::default() }
+	pub fn new(
+		inner: I,
+		client: Arc<C>,
+		select_chain: SC,
+		algorithm: A,
+		limits: ChainLimits<B>,
+		registry: Option<&Registry>,
+	) -> Self {
+		let metrics = registry.and_then(|registry| match metrics::Metrics::register(registry) {
+			Ok(metrics) => Some(metrics),
+			Err(e) => {
+				warn!(target: LOG_TARGET, "Failed to register fork choice metrics: {}", e);
+				None
+			},
+		});
+
+		Self {
+			inner,
+			client,
+			select_chain,
+			algorithm,
+			limits,
+			metrics,
+			reorgs: Default::default(),
+			_marker: Default::default(),
+		}
+	}
+
+	/// Handle to subscribe to the best chain reorgs caused by this block import.
+	pub fn reorg_notifications(&self) -> ReorgNotifications<B> {
+		self.reorgs.clone()
+	}
+}
+
+impl<B, I, C, SC, A> PowBlockImport<B, I, C, SC, A>
+where
+	B: BlockT,
+	C: HeaderMetadata<B, Error = sp_blockchain::Error>,
+{
+	/// Check that a block at a checkpoint height is the checkpoint.
+	fn check_checkpoint(&self, number: NumberFor<B>, hash: B::Hash) -> Result<(), Error<B>> {
+		match self.limits.checkpoints.get(&number) {
+			Some(checkpoint) if *checkpoint != hash =>
+				Err(Error::CheckpointMismatch(number, *checkpoint)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Check that switching the best chain to a child of `parent_hash` stays within the limits,
+	/// returning the common ancestor of both chains.
+	fn check_reorg(
+		&self,
+		best_header: &B::Header,
+		parent_hash: B::Hash,
+	) -> Result<HashAndNumber<B>, Error<B>> {
+		let best_number = *best_header.number();
+		let ancestor =
+			lowest_common_ancestor(self.client.as_ref(), best_header.hash(), parent_hash)
+				.map_err(Error::Client)?;
+		let depth = best_number.saturating_sub(ancestor.number);
+
+		if let Some(max_reorg_depth) = self.limits.max_reorg_depth {
+			if depth > max_reorg_depth {
+				return Err(Error::ReorgTooDeep(depth, max_reorg_depth))
+			}
+		}
+		if let Some((&number, _)) = self.limits.checkpoints.range(..=best_number).next_back() {
+			if ancestor.number < number {
+				return Err(Error::CheckpointReverted(number))
+			}
+		}
+
+		Ok(ancestor)
 	}
 }
 
@@ -252,10 +381,11 @@ where
 	I: BlockImport<B> + Send + Sync,
 	I::Error: Into<ConsensusError>,
 	C: ProvideRuntimeApi<B> + Send + Sync + HeaderBackend<B> + AuxStore + BlockOf,
+	C: HeaderMetadata<B, Error = sp_blockchain::Error>,
 	C::Api: BlockBuilderApi<B>,
 	SC: SelectChain<B>,
 	A: PowAlgorithm<B> + Send + Sync,
-	A::Difficulty: Send + 'static,
+	A::Difficulty: UniqueSaturatedInto<u128> + Send + 'static,
 {
 	type Error = ConsensusError;
 
@@ -284,6 +414,8 @@ where
 
 		let inner_seal = fetch_seal::<B>(block.post_digests.last(), block.header.hash())?;
 
+		self.check_checkpoint(*block.header.number(), block.post_hash())?;
+
 		let intermediate =
 			block.remove_intermediate::<PowIntermediate<A::Difficulty>>(INTERMEDIATE_KEY)?;
 
@@ -315,16 +447,67 @@ where
 					Ordering::Less => false,
 					Ordering::Greater => true,
 					Ordering::Equal => {
-						let best_inner_seal =
-							fetch_seal::<B>(best_header.digest().logs.last(), best_hash)?;
-
-						self.algorithm.break_tie(&best_inner_seal, &inner_seal)
+						let mut best_pre_header = best_header.clone();
+						let best_inner_seal = fetch_seal::<B>(
+							best_pre_header.digest_mut().pop().as_ref(),
+							best_hash,
+						)?;
+						let best_pre_hash = best_pre_header.hash();
+
+						self.algorithm.break_tie(
+							&best_pre_hash,
+							&best_inner_seal,
+							&pre_hash,
+							&inner_seal,
+						)
 					},
 				},
 			));
 		}
 
-		self.inner.import_block(block).await.map_err(Into::into)
+		// Deep reorgs are rejected rather than imported as a fork, so that the chain cannot be
+		// extended further.
+		let is_best = matches!(block.fork_choice, Some(ForkChoiceStrategy::Custom(true)));
+		let common_ancestor = match is_best && parent_hash != best_hash {
+			true => Some(self.check_reorg(&best_header, parent_hash)?),
+			false => None,
+		};
+
+		let hash = block.post_hash();
+		let number = *block.header.number();
+		let result = self.inner.import_block(block).await.map_err(Into::into)?;
+		if !matches!(result, ImportResult::Imported(_)) {
+			return Ok(result)
+		}
+
+		if let Some(metrics) = &self.metrics {
+			metrics.report_import(is_best, aux.total_difficulty.saturated_into::<u128>() as f64);
+		}
+		if let Some(common_ancestor) = common_ancestor {
+			let depth = best_header.number().saturating_sub(common_ancestor.number);
+			info!(
+				target: LOG_TARGET,
+				"Reorg of {} blocks from #{} ({:?}) to #{} ({:?}), common ancestor #{} ({:?})",
+				depth,
+				best_header.number(),
+				best_hash,
+				number,
+				hash,
+				common_ancestor.number,
+				common_ancestor.hash,
+			);
+			if let Some(metrics) = &self.metrics {
+				metrics.report_reorg(depth.saturated_into());
+			}
+			self.reorgs.notify(ReorgEvent {
+				old_best: (best_hash, *best_header.number()),
+				new_best: (hash, number),
+				common_ancestor: (common_ancestor.hash, common_ancestor.number),
+				depth,
+			});
+		}
+
+		Ok(result)
 	}
 }
 
@@ -362,8 +545,13 @@ where
 		};
 
 		let pre_hash = header.hash();
+		let pre_digest = find_pre_digest::<B>(&header)?;
 
-		if !self.algorithm.preliminary_verify(&pre_hash, &inner_seal)?.unwrap_or(true) {
+		if !self
+			.algorithm
+			.preliminary_verify(&header, &pre_hash, pre_digest.as_deref(), &inner_seal)?
+			.unwrap_or(true)
+		{
 			return Err(Error::FailedPreliminaryVerify)
 		}
 
@@ -450,9 +638,6 @@ where
 	}
 }
 
-/// The PoW import queue type.
-pub type PowImportQueue<B> = BasicQueue<B>;
-
 /// Parameters passed to [`import_queue`].
 pub struct ImportQueueParams<'a, B, I, C, A, CIDP, S> {
 	/// The block import to use.
@@ -469,6 +654,8 @@ pub struct ImportQueueParams<'a, B, I, C, A, CIDP, S> {
 	pub spawner: &'a S,
 	/// The prometheus registry.
 	pub registry: Option<&'a Registry>,
+	/// Number of threads to verify seals ahead of block import. Zero disables it.
+	pub precompute_threads: usize,
 }
 
 /// Import queue for PoW engine.
@@ -481,6 +668,7 @@ pub fn import_queue<B, I, C, A, CIDP, S>(
 		create_inherent_data_providers,
 		spawner,
 		registry,
+		precompute_threads,
 	}: ImportQueueParams<B, I, C, A, CIDP, S>,
 ) -> Result<PowImportQueue<B>, sp_consensus::Error>
 where
@@ -493,9 +681,11 @@ where
 	CIDP: CreateInherentDataProviders<B, ()> + Send + 'static,
 	S: sp_core::traits::SpawnEssentialNamed,
 {
-	let verifier = PowVerifier::new(client, algorithm, create_inherent_data_providers);
+	let verifier = PowVerifier::new(client, algorithm.clone(), create_inherent_data_providers);
+	let queue =
+		BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry);
 
-	Ok(BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry))
+	Ok(PowImportQueue::new(queue, algorithm, precompute_threads, spawner))
 }
 
 /// Parameters used to start a mining worker.
@@ -777,3 +967,145 @@ impl<B: BlockT> PreRuntimeProvider<B> for EmptyPreRuntimeProvider<B> {
 		Ok(Vec::new())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_blockchain::CachedHeaderMetadata;
+	use sp_core::H256;
+	use sp_runtime::testing::{Block, ExtrinsicWrapper, Header};
+	use std::collections::HashMap;
+
+	type TestBlock = Block<ExtrinsicWrapper<u64>>;
+
+	#[derive(Default)]
+	struct TestChain {
+		headers: HashMap<H256, Header>,
+	}
+
+	impl TestChain {
+		/// Appends blocks on top of `parent`, returning their headers. Blocks of different forks
+		/// differ by their state root.
+		fn extend(&mut self, parent: Option<&Header>, len: u64, fork: u8) -> Vec<Header> {
+			let (mut number, mut parent_hash) =
+				parent.map_or((0, H256::zero()), |parent| (parent.number + 1, parent.hash()));
+			let mut headers = Vec::new();
+			for _ in 0..len {
+				let header = Header::new(
+					number,
+					Default::default(),
+					H256::repeat_byte(fork),
+					parent_hash,
+					Default::default(),
+				);
+				self.headers.insert(header.hash(), header.clone());
+				(number, parent_hash) = (number + 1, header.hash());
+				headers.push(header);
+			}
+			headers
+		}
+	}
+
+	impl HeaderMetadata<TestBlock> for TestChain {
+		type Error = sp_blockchain::Error;
+
+		fn header_metadata(
+			&self,
+			hash: H256,
+		) -> Result<CachedHeaderMetadata<TestBlock>, Self::Error> {
+			self.headers
+				.get(&hash)
+				.map(CachedHeaderMetadata::from)
+				.ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("{:?}", hash)))
+		}
+
+		fn insert_header_metadata(&self, _hash: H256, _metadata: CachedHeaderMetadata<TestBlock>) {}
+
+		fn remove_header_metadata(&self, _hash: H256) {}
+	}
+
+	fn block_import(
+		chain: TestChain,
+		limits: ChainLimits<TestBlock>,
+	) -> PowBlockImport<TestBlock, (), TestChain, (), ()> {
+		PowBlockImport {
+			inner: (),
+			client: Arc::new(chain),
+			select_chain: (),
+			algorithm: (),
+			limits,
+			metrics: None,
+			reorgs: Default::default(),
+			_marker: Default::default(),
+		}
+	}
+
+	/// Returns a main chain of blocks #0 to #5, and a fork of blocks #2 to #4 on top of #1.
+	fn forked_chain() -> (TestChain, Vec<Header>, Vec<Header>) {
+		let mut chain = TestChain::default();
+		let main = chain.extend(None, 6, 0);
+		let fork = chain.extend(Some(&main[1]), 3, 1);
+		(chain, main, fork)
+	}
+
+	#[test]
+	fn checkpoint_must_match() {
+		let checkpoint = H256::repeat_byte(1);
+		let block_import = block_import(
+			TestChain::default(),
+			ChainLimits { checkpoints: [(2, checkpoint)].into(), max_reorg_depth: None },
+		);
+
+		assert!(block_import.check_checkpoint(2, checkpoint).is_ok());
+		assert!(block_import.check_checkpoint(3, H256::repeat_byte(2)).is_ok());
+		assert!(matches!(
+			block_import.check_checkpoint(2, H256::repeat_byte(2)),
+			Err(Error::CheckpointMismatch(2, hash)) if hash == checkpoint
+		));
+	}
+
+	#[test]
+	fn reorg_within_depth_returns_common_ancestor() {
+		let (chain, main, fork) = forked_chain();
+		let block_import =
+			block_import(chain, ChainLimits { checkpoints: [].into(), max_reorg_depth: Some(4) });
+
+		let ancestor = block_import.check_reorg(&main[5], fork[2].hash()).unwrap();
+		assert_eq!((ancestor.hash, ancestor.number), (main[1].hash(), 1));
+	}
+
+	#[test]
+	fn reorg_too_deep_is_rejected() {
+		let (chain, main, fork) = forked_chain();
+		let block_import =
+			block_import(chain, ChainLimits { checkpoints: [].into(), max_reorg_depth: Some(3) });
+
+		assert!(matches!(
+			block_import.check_reorg(&main[5], fork[2].hash()),
+			Err(Error::ReorgTooDeep(4, 3))
+		));
+		// Extending the best chain is not a reorg.
+		assert!(block_import.check_reorg(&main[4], main[4].hash()).is_ok());
+	}
+
+	#[test]
+	fn reorg_reverting_checkpoint_is_rejected() {
+		let (chain, main, fork) = forked_chain();
+		let checkpoints = [(2, main[2].hash())].into();
+		let block_import = block_import(chain, ChainLimits { checkpoints, max_reorg_depth: None });
+
+		assert!(matches!(
+			block_import.check_reorg(&main[5], fork[2].hash()),
+			Err(Error::CheckpointReverted(2))
+		));
+	}
+
+	#[test]
+	fn checkpoints_above_best_block_are_ignored() {
+		let (chain, main, fork) = forked_chain();
+		let checkpoints = [(1, main[1].hash()), (8, H256::repeat_byte(8))].into();
+		let block_import = block_import(chain, ChainLimits { checkpoints, max_reorg_depth: None });
+
+		assert!(block_import.check_reorg(&main[5], fork[2].hash()).is_ok());
+	}
+}
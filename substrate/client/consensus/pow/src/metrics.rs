@@ -0,0 +1,79 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+//! Fork choice metrics of [`PowBlockImport`](crate::PowBlockImport).
+
+use substrate_prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, Histogram, HistogramOpts, Opts, PrometheusError,
+	Registry, F64, U64,
+};
+
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	imported_blocks: CounterVec<U64>,
+	orphaned_blocks: Counter<U64>,
+	reorgs: Counter<U64>,
+	reorg_depth: Histogram,
+	best_total_difficulty: Gauge<F64>,
+}
+
+impl Metrics {
+	pub(crate) fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			imported_blocks: register(
+				CounterVec::new(
+					Opts::new(
+						"substrate_pow_imported_blocks_total",
+						"Number of imported blocks by whether they became the best block",
+					),
+					&["fork_choice"],
+				)?,
+				registry,
+			)?,
+			orphaned_blocks: register(
+				Counter::new(
+					"substrate_pow_orphaned_blocks_total",
+					"Number of blocks removed from the best chain by reorgs",
+				)?,
+				registry,
+			)?,
+			reorgs: register(
+				Counter::new("substrate_pow_reorgs_total", "Number of best chain reorgs")?,
+				registry,
+			)?,
+			reorg_depth: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"substrate_pow_reorg_depth",
+						"Number of blocks removed from the best chain per reorg",
+					)
+					.buckets(vec![1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0]),
+				)?,
+				registry,
+			)?,
+			best_total_difficulty: register(
+				Gauge::new(
+					"substrate_pow_best_total_difficulty",
+					"Total difficulty of the best chain",
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Reports an imported block, with the total difficulty of its chain.
+	pub(crate) fn report_import(&self, is_best: bool, total_difficulty: f64) {
+		let fork_choice = if is_best { "best" } else { "fork" };
+		self.imported_blocks.with_label_values(&[fork_choice]).inc();
+		if is_best {
+			self.best_total_difficulty.set(total_difficulty);
+		}
+	}
+
+	/// Reports a reorg removing `depth` blocks from the best chain.
+	pub(crate) fn report_reorg(&self, depth: u64) {
+		self.reorgs.inc();
+		self.orphaned_blocks.inc_by(depth);
+		self.reorg_depth.observe(depth as f64);
+	}
+}
@@ -0,0 +1,424 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+//! Seal verification ahead of block import.
+//!
+//! Verifying a seal is usually far more expensive than the rest of the header checks, and does
+//! not depend on the state of the parent block. The import queue therefore hands each batch of
+//! incoming headers to a pool of blocking tasks, which compute the work of the seals in parallel
+//! through [`PowAlgorithm::precompute`]. Algorithms keep the results in a [`WorkCache`], so that
+//! [`PowAlgorithm::verify`] only compares them against the difficulty during sequential import.
+
+use crate::{fetch_seal, PowAlgorithm, LOG_TARGET};
+use log::*;
+use parking_lot::{Condvar, Mutex};
+use sc_consensus::{
+	import_queue::{ImportQueueService, RuntimeOrigin},
+	BasicQueue, ImportQueue, IncomingBlock, Link,
+};
+use schnellru::{ByLength, LruMap};
+use sp_consensus::BlockOrigin;
+use sp_core::traits::SpawnEssentialNamed;
+use sp_runtime::{
+	traits::{Block as BlockT, Header as HeaderT, NumberFor},
+	Justifications,
+};
+use std::{
+	hash::Hash,
+	sync::{mpsc, Arc},
+};
+
+enum Entry<W> {
+	Pending,
+	Ready(W),
+}
+
+/// Cache of seal work shared between the import queue workers and block import.
+pub struct WorkCache<K: Hash + PartialEq, W> {
+	entries: Arc<(Mutex<LruMap<K, Entry<W>>>, Condvar)>,
+}
+
+impl<K: Hash + PartialEq, W> Clone for WorkCache<K, W> {
+	fn clone(&self) -> Self {
+		Self { entries: self.entries.clone() }
+	}
+}
+
+impl<K: Hash + PartialEq + Clone, W: Clone> WorkCache<K, W> {
+	/// Create a cache holding up to `capacity` results.
+	pub fn new(capacity: u32) -> Self {
+		Self {
+			entries: Arc::new((Mutex::new(LruMap::new(ByLength::new(capacity))), Condvar::new())),
+		}
+	}
+
+	/// Return the cached work for the key, or compute it with `f`.
+	///
+	/// If another thread is computing the same work, this waits for its result instead.
+	pub fn get_or_compute<E>(&self, key: &K, f: impl FnOnce() -> Result<W, E>) -> Result<W, E> {
+		let (entries, computed) = &*self.entries;
+
+		let mut guard = entries.lock();
+		loop {
+			match guard.get(key) {
+				Some(Entry::Ready(work)) => return Ok(work.clone()),
+				Some(Entry::Pending) => computed.wait(&mut guard),
+				None => break,
+			}
+		}
+		guard.insert(key.clone(), Entry::Pending);
+		drop(guard);
+
+		let result = f();
+
+		let mut guard = entries.lock();
+		match &result {
+			Ok(work) => {
+				guard.insert(key.clone(), Entry::Ready(work.clone()));
+			},
+			Err(_) => {
+				guard.remove(key);
+			},
+		}
+		computed.notify_all();
+
+		result
+	}
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+
+/// Maximum number of headers waiting for the precompute workers.
+///
+/// Headers arriving while the queue is full are not precomputed, and their seals are verified
+/// during import instead.
+const MAX_PENDING_TASKS: usize = 1024;
+
+/// Import queue service which submits incoming headers to the precompute workers before
+/// forwarding them to the inner queue.
+struct PrecomputeService<B: BlockT, A> {
+	inner: Box<dyn ImportQueueService<B>>,
+	algorithm: A,
+	tasks: mpsc::SyncSender<Task>,
+}
+
+impl<B, A> PrecomputeService<B, A>
+where
+	B: BlockT,
+	A: PowAlgorithm<B> + Clone + Send + Sync + 'static,
+{
+	fn precompute(&self, blocks: &[IncomingBlock<B>]) {
+		let headers =
+			Arc::new(blocks.iter().filter_map(|block| block.header.clone()).collect::<Vec<_>>());
+
+		// Tasks are queued in import order, so that the workers are ahead of block import.
+		for index in 0..headers.len() {
+			let headers = headers.clone();
+			let algorithm = self.algorithm.clone();
+			let task = Box::new(move || {
+				let mut header = headers[index].clone();
+				let hash = header.hash();
+				let seal = match fetch_seal::<B>(header.digest_mut().pop().as_ref(), hash) {
+					Ok(seal) => seal,
+					Err(_) => return,
+				};
+				let pre_hash = header.hash();
+
+				if let Err(e) = algorithm.precompute(&pre_hash, &seal, &header, &headers[..index]) {
+					debug!(target: LOG_TARGET, "Failed to precompute seal of {:?}: {}", hash, e);
+				}
+			});
+
+			match self.tasks.try_send(task) {
+				Ok(()) => {},
+				Err(mpsc::TrySendError::Full(_)) => {
+					debug!(
+						target: LOG_TARGET,
+						"Precompute queue is full, skipping {} header(s)",
+						headers.len() - index,
+					);
+					break
+				},
+				Err(mpsc::TrySendError::Disconnected(_)) => break,
+			}
+		}
+	}
+}
+
+impl<B, A> ImportQueueService<B> for PrecomputeService<B, A>
+where
+	B: BlockT,
+	A: PowAlgorithm<B> + Clone + Send + Sync + 'static,
+{
+	fn import_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
+		self.precompute(&blocks);
+		self.inner.import_blocks(origin, blocks);
+	}
+
+	fn import_justifications(
+		&mut self,
+		who: RuntimeOrigin,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		justifications: Justifications,
+	) {
+		self.inner.import_justifications(who, hash, number, justifications);
+	}
+}
+
+/// Import queue for PoW engine, which verifies seals on worker threads ahead of block import.
+pub struct PowImportQueue<B: BlockT> {
+	inner: BasicQueue<B>,
+	service: Box<dyn ImportQueueService<B>>,
+	new_service:
+		Box<dyn Fn(Box<dyn ImportQueueService<B>>) -> Box<dyn ImportQueueService<B>> + Send>,
+}
+
+impl<B: BlockT> PowImportQueue<B> {
+	/// Wrap the basic queue, spawning `threads` blocking workers to precompute seals.
+	///
+	/// No workers are spawned if `threads` is zero, and blocks are imported as is.
+	pub(crate) fn new<A>(
+		inner: BasicQueue<B>,
+		algorithm: A,
+		threads: usize,
+		spawner: &impl SpawnEssentialNamed,
+	) -> Self
+	where
+		A: PowAlgorithm<B> + Clone + Send + Sync + 'static,
+	{
+		let (tasks, receiver) = mpsc::sync_channel::<Task>(MAX_PENDING_TASKS);
+		let receiver = Arc::new(Mutex::new(receiver));
+
+		for _ in 0..threads {
+			let receiver = receiver.clone();
+			spawner.spawn_essential_blocking(
+				"pow-precompute-worker",
+				Some("block-import"),
+				Box::pin(async move {
+					loop {
+						// The workers stop when the queue and all of its services are dropped.
+						let task = match receiver.lock().recv() {
+							Ok(task) => task,
+							Err(_) => break,
+						};
+						task();
+					}
+				}),
+			);
+		}
+
+		let new_service: Box<
+			dyn Fn(Box<dyn ImportQueueService<B>>) -> Box<dyn ImportQueueService<B>> + Send,
+		> = match threads {
+			0 => Box::new(|inner| inner),
+			_ => Box::new(move |inner| {
+				Box::new(PrecomputeService {
+					inner,
+					algorithm: algorithm.clone(),
+					tasks: tasks.clone(),
+				})
+			}),
+		};
+		let service = new_service(inner.service());
+
+		Self { inner, service, new_service }
+	}
+}
+
+#[async_trait::async_trait]
+impl<B: BlockT> ImportQueue<B> for PowImportQueue<B> {
+	fn service(&self) -> Box<dyn ImportQueueService<B>> {
+		(self.new_service)(self.inner.service())
+	}
+
+	fn service_ref(&mut self) -> &mut dyn ImportQueueService<B> {
+		&mut *self.service
+	}
+
+	fn poll_actions(&mut self, cx: &mut futures::task::Context, link: &mut dyn Link<B>) {
+		self.inner.poll_actions(cx, link)
+	}
+
+	async fn run(self, link: Box<dyn Link<B>>) {
+		self.inner.run(link).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Error, Seal};
+	use sp_consensus_pow::POW_ENGINE_ID;
+	use sp_core::H256;
+	use sp_runtime::{
+		generic::BlockId,
+		testing::{Block, ExtrinsicWrapper, Header},
+		Digest, DigestItem,
+	};
+
+	type TestBlock = Block<ExtrinsicWrapper<u64>>;
+
+	#[derive(Clone)]
+	struct TestAlgorithm {
+		cache: WorkCache<H256, Seal>,
+	}
+
+	impl PowAlgorithm<TestBlock> for TestAlgorithm {
+		type Difficulty = u128;
+
+		fn difficulty(&self, _parent: H256) -> Result<u128, Error<TestBlock>> {
+			Ok(1)
+		}
+
+		fn precompute(
+			&self,
+			pre_hash: &H256,
+			seal: &Seal,
+			_header: &Header,
+			_queued: &[Header],
+		) -> Result<(), Error<TestBlock>> {
+			self.cache.get_or_compute(pre_hash, || Ok(seal.clone())).map(|_| ())
+		}
+
+		fn verify(
+			&self,
+			_parent: &BlockId<TestBlock>,
+			_pre_hash: &H256,
+			_pre_digest: Option<&[u8]>,
+			_seal: &Seal,
+			_difficulty: u128,
+		) -> Result<bool, Error<TestBlock>> {
+			Ok(true)
+		}
+	}
+
+	/// Inner queue service running the queued tasks on import, and recording which headers
+	/// had their work cached by then.
+	struct TestService {
+		receiver: Arc<Mutex<mpsc::Receiver<Task>>>,
+		cache: WorkCache<H256, Seal>,
+		imported: Arc<Mutex<Vec<(H256, Option<Seal>)>>>,
+	}
+
+	impl ImportQueueService<TestBlock> for TestService {
+		fn import_blocks(&mut self, _origin: BlockOrigin, blocks: Vec<IncomingBlock<TestBlock>>) {
+			for task in self.receiver.lock().try_iter() {
+				task();
+			}
+			for block in blocks {
+				let mut header = block.header.expect("Test blocks have headers");
+				header.digest_mut().pop();
+				let pre_hash = header.hash();
+				let work = self.cache.get_or_compute(&pre_hash, || Err::<Seal, ()>(())).ok();
+				self.imported.lock().push((pre_hash, work));
+			}
+		}
+
+		fn import_justifications(
+			&mut self,
+			_who: RuntimeOrigin,
+			_hash: H256,
+			_number: u64,
+			_justifications: Justifications,
+		) {
+		}
+	}
+
+	fn incoming_block(number: u64, seal: Seal) -> IncomingBlock<TestBlock> {
+		let digest = Digest { logs: vec![DigestItem::Seal(POW_ENGINE_ID, seal)] };
+		let header =
+			Header::new(number, Default::default(), Default::default(), Default::default(), digest);
+		IncomingBlock {
+			hash: header.hash(),
+			header: Some(header),
+			body: None,
+			indexed_body: None,
+			justifications: None,
+			origin: None,
+			allow_missing_state: false,
+			skip_execution: false,
+			import_existing: false,
+			state: None,
+		}
+	}
+
+	#[test]
+	fn seals_are_precomputed_before_import() {
+		let cache = WorkCache::new(8);
+		let (tasks, receiver) = mpsc::sync_channel(MAX_PENDING_TASKS);
+		let imported = Arc::new(Mutex::new(Vec::new()));
+		let mut service = PrecomputeService {
+			inner: Box::new(TestService {
+				receiver: Arc::new(Mutex::new(receiver)),
+				cache: cache.clone(),
+				imported: imported.clone(),
+			}),
+			algorithm: TestAlgorithm { cache },
+			tasks,
+		};
+
+		service.import_blocks(
+			BlockOrigin::NetworkInitialSync,
+			vec![incoming_block(1, vec![1]), incoming_block(2, vec![2])],
+		);
+
+		let imported = imported.lock();
+		assert_eq!(imported.len(), 2);
+		assert_eq!(imported[0].1, Some(vec![1]));
+		assert_eq!(imported[1].1, Some(vec![2]));
+	}
+
+	#[test]
+	fn headers_are_skipped_when_queue_is_full() {
+		let cache = WorkCache::new(8);
+		let (tasks, receiver) = mpsc::sync_channel(1);
+		let imported = Arc::new(Mutex::new(Vec::new()));
+		let mut service = PrecomputeService {
+			inner: Box::new(TestService {
+				receiver: Arc::new(Mutex::new(receiver)),
+				cache: cache.clone(),
+				imported: imported.clone(),
+			}),
+			algorithm: TestAlgorithm { cache },
+			tasks,
+		};
+
+		service.import_blocks(
+			BlockOrigin::NetworkInitialSync,
+			vec![incoming_block(1, vec![1]), incoming_block(2, vec![2])],
+		);
+
+		let imported = imported.lock();
+		assert_eq!(imported[0].1, Some(vec![1]));
+		assert_eq!(imported[1].1, None);
+	}
+
+	#[test]
+	fn work_is_computed_once() {
+		let cache = WorkCache::<u64, u64>::new(8);
+
+		assert_eq!(cache.get_or_compute(&1, || Ok::<_, ()>(10)), Ok(10));
+		assert_eq!(cache.get_or_compute(&1, || Err(())), Ok(10));
+	}
+
+	#[test]
+	fn pending_work_is_awaited() {
+		let cache = WorkCache::<u64, u64>::new(8);
+		let (started_tx, started_rx) = mpsc::channel();
+
+		std::thread::scope(|s| {
+			let worker = s.spawn(|| {
+				cache.get_or_compute(&1, || {
+					started_tx.send(()).unwrap();
+					std::thread::sleep(std::time::Duration::from_millis(50));
+					Ok::<_, ()>(10)
+				})
+			});
+			started_rx.recv().unwrap();
+
+			assert_eq!(cache.get_or_compute(&1, || Ok::<_, ()>(20)), Ok(10));
+			assert_eq!(worker.join().unwrap(), Ok(10));
+		});
+	}
+}
@@ -0,0 +1,55 @@
+// Copyright (c) Ryuichi Sakamoto
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+//! Notifications of best chain reorgs by [`PowBlockImport`](crate::PowBlockImport).
+
+use futures::channel::mpsc;
+use parking_lot::Mutex;
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+use std::sync::Arc;
+
+/// Switch of the best chain to a block which does not extend the previous best block.
+#[derive(Clone, Debug)]
+pub struct ReorgEvent<B: BlockT> {
+	/// Previous best block.
+	pub old_best: (B::Hash, NumberFor<B>),
+	/// New best block.
+	pub new_best: (B::Hash, NumberFor<B>),
+	/// Last block shared by the previous and new best chains.
+	pub common_ancestor: (B::Hash, NumberFor<B>),
+	/// Number of blocks removed from the best chain.
+	pub depth: NumberFor<B>,
+}
+
+/// Handle to subscribe to reorg events, shared by the clones of a block import.
+pub struct ReorgNotifications<B: BlockT> {
+	subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<ReorgEvent<B>>>>>,
+}
+
+impl<B: BlockT> Clone for ReorgNotifications<B> {
+	fn clone(&self) -> Self {
+		Self { subscribers: self.subscribers.clone() }
+	}
+}
+
+impl<B: BlockT> Default for ReorgNotifications<B> {
+	fn default() -> Self {
+		Self { subscribers: Default::default() }
+	}
+}
+
+impl<B: BlockT> ReorgNotifications<B> {
+	/// Subscribe to reorg events from now on.
+	pub fn subscribe(&self) -> mpsc::UnboundedReceiver<ReorgEvent<B>> {
+		let (sender, receiver) = mpsc::unbounded();
+		self.subscribers.lock().push(sender);
+		receiver
+	}
+
+	/// Send an event to the subscribers, dropping those which have gone away.
+	pub(crate) fn notify(&self, event: ReorgEvent<B>) {
+		self.subscribers
+			.lock()
+			.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
+	}
+}